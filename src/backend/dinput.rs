@@ -0,0 +1,430 @@
+use windows::Win32::Devices::HumanInterfaceDevice::{
+    DirectInput8Create, IDirectInput8W, IDirectInputDevice8W,
+    DIDEVICEINSTANCEW, DIDATAFORMAT, DIOBJECTDATAFORMAT,
+    DISCL_BACKGROUND, DISCL_NONEXCLUSIVE,
+    DI8DEVCLASS_GAMECTRL, DIEDFL_ATTACHEDONLY,
+    DIDF_ABSAXIS, DIPROP_RANGE, DIPROPRANGE, DIPROPHEADER,
+    DIPH_BYOFFSET, DIERR_UNPLUGGED,
+};
+use windows::Win32::Foundation::{HINSTANCE, BOOL};
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::{GUID, Interface};
+use std::cell::RefCell;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use super::{Button, Buttons, DeviceId, DeviceInfo, GamepadDevice, GamepadState, InputBackend, PollError};
+
+// Button order most Xbox-style pads report through DirectInput
+const BUTTON_ORDER: [Button; 10] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::Back,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+];
+
+const DIRECTINPUT_VERSION: u32 = 0x0800;
+
+// Standard DirectInput axis GUIDs
+static GUID_XAXIS: GUID = GUID { data1: 0xA36D02E0, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_YAXIS: GUID = GUID { data1: 0xA36D02E1, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_ZAXIS: GUID = GUID { data1: 0xA36D02E2, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_RXAXIS: GUID = GUID { data1: 0xA36D02F4, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_RYAXIS: GUID = GUID { data1: 0xA36D02F5, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_RZAXIS: GUID = GUID { data1: 0xA36D02E3, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_SLIDER: GUID = GUID { data1: 0xA36D02E4, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_POV: GUID = GUID { data1: 0xA36D02E5, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+static GUID_BUTTON: GUID = GUID { data1: 0xA36D02F0, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
+
+// DIJOYSTATE structure - standard DirectInput joystick format (80 bytes)
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DIJoyState {
+    x: i32,      // 0
+    y: i32,      // 4
+    z: i32,      // 8
+    rx: i32,     // 12
+    ry: i32,     // 16
+    rz: i32,     // 20
+    slider: [i32; 2], // 24, 28
+    pov: [u32; 4],    // 32, 36, 40, 44
+    buttons: [u8; 32], // 48-79
+}
+
+impl Default for DIJoyState {
+    fn default() -> Self {
+        Self {
+            x: 32767, y: 32767, z: 32767,  // Center values
+            rx: 32767, ry: 32767, rz: 32767,
+            slider: [32767; 2],
+            pov: [0xFFFFFFFF; 4], // -1 = centered
+            buttons: [0; 32],
+        }
+    }
+}
+
+// Build c_dfDIJoystick equivalent data format with proper GUIDs
+fn create_joystick_data_format() -> (DIDATAFORMAT, Vec<DIOBJECTDATAFORMAT>) {
+    // DIDFT constants
+    const DIDFT_ABSAXIS: u32 = 0x00000002;
+    const DIDFT_POV: u32 = 0x00000010;
+    const DIDFT_PSHBUTTON: u32 = 0x00000004;
+    const DIDFT_OPTIONAL: u32 = 0x80000000;
+    const DIDFT_ANYINSTANCE: u32 = 0x00FFFF00;
+
+    let mut objects = Vec::new();
+
+    // X axis at offset 0
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_XAXIS as *const GUID,
+        dwOfs: 0,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // Y axis at offset 4
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_YAXIS as *const GUID,
+        dwOfs: 4,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // Z axis at offset 8
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_ZAXIS as *const GUID,
+        dwOfs: 8,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // Rx axis at offset 12
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_RXAXIS as *const GUID,
+        dwOfs: 12,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // Ry axis at offset 16
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_RYAXIS as *const GUID,
+        dwOfs: 16,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // Rz axis at offset 20
+    objects.push(DIOBJECTDATAFORMAT {
+        pguid: &GUID_RZAXIS as *const GUID,
+        dwOfs: 20,
+        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+        dwFlags: 0,
+    });
+    // 2 sliders at offsets 24, 28
+    for i in 0..2u32 {
+        objects.push(DIOBJECTDATAFORMAT {
+            pguid: &GUID_SLIDER as *const GUID,
+            dwOfs: 24 + i * 4,
+            dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
+            dwFlags: 0,
+        });
+    }
+    // 4 POVs at offsets 32, 36, 40, 44
+    for i in 0..4u32 {
+        objects.push(DIOBJECTDATAFORMAT {
+            pguid: &GUID_POV as *const GUID,
+            dwOfs: 32 + i * 4,
+            dwType: DIDFT_OPTIONAL | DIDFT_POV | DIDFT_ANYINSTANCE,
+            dwFlags: 0,
+        });
+    }
+    // 32 buttons at offsets 48-79
+    for i in 0..32u32 {
+        objects.push(DIOBJECTDATAFORMAT {
+            pguid: &GUID_BUTTON as *const GUID,
+            dwOfs: 48 + i,
+            dwType: DIDFT_OPTIONAL | DIDFT_PSHBUTTON | DIDFT_ANYINSTANCE,
+            dwFlags: 0,
+        });
+    }
+
+    let format = DIDATAFORMAT {
+        dwSize: mem::size_of::<DIDATAFORMAT>() as u32,
+        dwObjSize: mem::size_of::<DIOBJECTDATAFORMAT>() as u32,
+        dwFlags: DIDF_ABSAXIS,
+        dwDataSize: mem::size_of::<DIJoyState>() as u32,
+        dwNumObjs: objects.len() as u32,
+        rgodf: std::ptr::null_mut(),
+    };
+
+    (format, objects)
+}
+
+struct DirectInputContext {
+    dinput: IDirectInput8W,
+}
+
+impl DirectInputContext {
+    fn new() -> Option<Self> {
+        unsafe {
+            // Initialize COM
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
+            // Get module handle
+            let hinst: HINSTANCE = GetModuleHandleW(None).ok()?.into();
+
+            // Create DirectInput8 object
+            let mut dinput: Option<IDirectInput8W> = None;
+            let hr = DirectInput8Create(
+                hinst,
+                DIRECTINPUT_VERSION,
+                &IDirectInput8W::IID,
+                &mut dinput as *mut _ as *mut *mut std::ffi::c_void,
+                None,
+            );
+
+            if hr.is_err() {
+                println!("Failed to create DirectInput8: {:?}", hr);
+                return None;
+            }
+
+            let dinput = dinput?;
+
+            Some(DirectInputContext { dinput })
+        }
+    }
+
+    fn enumerate_devices(&mut self) -> Vec<(GUID, String)> {
+        // We need to use a thread_local for the callback trampoline
+        thread_local! {
+            static GUIDS: RefCell<Vec<(GUID, String)>> = const { RefCell::new(Vec::new()) };
+        }
+
+        GUIDS.with(|g| g.borrow_mut().clear());
+
+        unsafe {
+            extern "system" fn enum_callback(
+                device_instance: *mut DIDEVICEINSTANCEW,
+                _context: *mut std::ffi::c_void,
+            ) -> BOOL {
+                unsafe {
+                    if !device_instance.is_null() {
+                        let instance = &*device_instance;
+                        let name_len = instance.tszInstanceName.iter().position(|&c| c == 0).unwrap_or(instance.tszInstanceName.len());
+                        let name = String::from_utf16_lossy(&instance.tszInstanceName[..name_len]);
+                        GUIDS.with(|g| g.borrow_mut().push((instance.guidInstance, name)));
+                    }
+                }
+                BOOL(1) // DIENUM_CONTINUE
+            }
+
+            let _ = self.dinput.EnumDevices(
+                DI8DEVCLASS_GAMECTRL,
+                Some(enum_callback),
+                std::ptr::null_mut(),
+                DIEDFL_ATTACHEDONLY,
+            );
+        }
+
+        GUIDS.with(|g| g.borrow().clone())
+    }
+
+    fn create_device(&mut self, guid: &GUID) -> Option<IDirectInputDevice8W> {
+        unsafe {
+            let mut device: Option<IDirectInputDevice8W> = None;
+
+            self.dinput
+                .CreateDevice(
+                    guid,
+                    &mut device,
+                    None, // pUnkOuter (almost always None)
+                )
+                .ok()?;
+            
+            let device = device?;
+
+            // Set cooperative level (background + nonexclusive)
+            device.SetCooperativeLevel(
+                None, // HWND - None for background
+                DISCL_BACKGROUND | DISCL_NONEXCLUSIVE,
+            ).ok()?;
+
+            // Set data format using c_dfDIJoystick equivalent
+            let (mut format, mut objects) = create_joystick_data_format();
+            format.rgodf = objects.as_mut_ptr();
+
+            let fmt_result = device.SetDataFormat(&mut format as *mut DIDATAFORMAT);
+            if let Err(e) = fmt_result {
+                println!("SetDataFormat failed: {:?}", e);
+                return None;
+            }
+            println!("SetDataFormat succeeded");
+
+            // Set axis range to 0-65535 (standard DirectInput range) for X/Y, Z (triggers) and Rx/Ry
+            // We'll convert to signed in poll
+            for offset in [0u32, 4u32, 8u32, 12u32, 16u32] {
+                let mut prop_range = DIPROPRANGE {
+                    diph: DIPROPHEADER {
+                        dwSize: mem::size_of::<DIPROPRANGE>() as u32,
+                        dwHeaderSize: mem::size_of::<DIPROPHEADER>() as u32,
+                        dwObj: offset,
+                        dwHow: DIPH_BYOFFSET,
+                    },
+                    lMin: 0,
+                    lMax: 65535,
+                };
+                let _ = device.SetProperty(
+                    &DIPROP_RANGE,
+                    &mut prop_range.diph as *mut DIPROPHEADER,
+                );
+            }
+
+            // Acquire the device
+            device.Acquire().ok()?;
+
+            Some(device)
+        }
+    }
+
+    fn read_device(device: &IDirectInputDevice8W) -> windows::core::Result<DIJoyState> {
+        unsafe {
+            // Poll the device first
+            let _ = device.Poll();
+
+            // Read joystick state
+            let mut state = DIJoyState::default();
+            device.GetDeviceState(
+                mem::size_of::<DIJoyState>() as u32,
+                &mut state as *mut _ as *mut std::ffi::c_void,
+            )?;
+
+            // Debug: print raw values
+            // println!("raw: x={} y={} z={} rx={} ry={} rz={}",
+            //     state.x, state.y, state.z, state.rx, state.ry, state.rz);
+
+            Ok(state)
+        }
+    }
+}
+
+pub struct DirectInputBackend {
+    ctx: DirectInputContext,
+}
+
+impl DirectInputBackend {
+    pub fn new() -> Option<Self> {
+        let ctx = DirectInputContext::new();
+        if ctx.is_none() {
+            println!("Failed to initialize DirectInput");
+        }
+        Some(DirectInputBackend { ctx: ctx? })
+    }
+}
+
+impl InputBackend for DirectInputBackend {
+    fn enumerate(&mut self) -> Vec<DeviceInfo> {
+        self.ctx
+            .enumerate_devices()
+            .into_iter()
+            .map(|(guid, name)| DeviceInfo {
+                id: DeviceId::Guid(guid.to_u128()),
+                name,
+                // Windows keeps the instance GUID for a controller across reboots
+                key: format!("dinput:{:032x}", guid.to_u128()),
+            })
+            .collect()
+    }
+
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+        match info.id {
+            DeviceId::Guid(guid) => {
+                let device = self.ctx.create_device(&GUID::from_u128(guid))?;
+                Some(Box::new(DirectInputDevice { device, failing_since: None }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads failing for this long mean the controller is gone, for drivers that
+/// never report DIERR_UNPLUGGED
+const LOST_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct DirectInputDevice {
+    device: IDirectInputDevice8W,
+    /// When reads started failing, `None` while they succeed
+    failing_since: Option<Instant>,
+}
+
+impl DirectInputDevice {
+    /// Input is also lost when the system sleeps or another app grabs the
+    /// device, so re-acquire and only give up once the device is unplugged
+    /// or has stayed unreadable for `LOST_TIMEOUT`.
+    fn recover(&mut self, error: windows::core::Error) -> PollError {
+        let since = *self.failing_since.get_or_insert_with(|| {
+            println!("GetDeviceState failed: {:?}", error);
+            Instant::now()
+        });
+
+        let reacquired = unsafe { self.device.Acquire() };
+        let unplugged = error.code().0 == DIERR_UNPLUGGED
+            || reacquired.is_err_and(|e| e.code().0 == DIERR_UNPLUGGED);
+        if unplugged || since.elapsed() > LOST_TIMEOUT {
+            PollError::Disconnected
+        } else {
+            PollError::NotReady
+        }
+    }
+}
+
+impl GamepadDevice for DirectInputDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        let state = match DirectInputContext::read_device(&self.device) {
+            Ok(state) => state,
+            Err(e) => return Err(self.recover(e)),
+        };
+        self.failing_since = None;
+
+        // Convert from DirectInput range (typically 0-65535 with center at 32767)
+        // to signed range (-32768 to 32767 with center at 0)
+        // DirectInput reports Y down, flip it to XInput's up positive.
+        // Xbox-style pads report the right stick on Rx/Ry.
+        let x = state.x - 32767;
+        let y = 32767 - state.y;
+        let rx = state.rx - 32767;
+        let ry = 32767 - state.ry;
+
+        // Xbox-style pads share one Z axis between the triggers: the left
+        // trigger pushes it above center and the right trigger below
+        let z = (state.z - 32767) as f32 / 32767.0;
+        let left_trigger = z.clamp(0.0, 1.0);
+        let right_trigger = (-z).clamp(0.0, 1.0);
+
+        let mut buttons = Buttons::default();
+        for (i, button) in BUTTON_ORDER.iter().enumerate() {
+            buttons.set(*button, state.buttons[i] & 0x80 != 0);
+        }
+
+        // POV is in hundredths of a degree clockwise from up, low word 0xFFFF when centered
+        if state.pov[0] & 0xFFFF != 0xFFFF {
+            let angle = state.pov[0] % 36000;
+            buttons.set(Button::DpadUp, angle > 27000 || angle < 9000);
+            buttons.set(Button::DpadRight, angle > 0 && angle < 18000);
+            buttons.set(Button::DpadDown, angle > 9000 && angle < 27000);
+            buttons.set(Button::DpadLeft, angle > 18000);
+        }
+
+        Ok(GamepadState {
+            left_stick: (x as f32, y as f32),
+            right_stick: (rx as f32, ry as f32),
+            left_trigger,
+            right_trigger,
+            buttons,
+            motion: None,
+            touchpad: None,
+        })
+    }
+}
@@ -0,0 +1,365 @@
+//! DSU ("cemuhook") client. Connects to a server such as DS4Windows or
+//! SteamDeckGyroDSU over UDP and exposes each connected slot as a device.
+//!
+//! Every packet starts with a 16 byte header: magic (`DSUC` from the client,
+//! `DSUS` from the server), protocol version, payload length, CRC32 of the
+//! whole packet with the CRC field zeroed, and the sender id. The payload
+//! starts with the message type.
+
+use std::cell::RefCell;
+use std::net::UdpSocket;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::{Button, Buttons, DeviceId, DeviceInfo, GamepadDevice, GamepadState, InputBackend, Motion, PollError};
+
+const PROTOCOL_VERSION: u16 = 1001;
+const HEADER_LEN: usize = 16;
+
+const MSG_VERSION: u32 = 0x100000;
+const MSG_PORT_INFO: u32 = 0x100001;
+const MSG_PAD_DATA: u32 = 0x100002;
+
+/// Servers stop sending pad data a few seconds after the last request
+const SUBSCRIBE_INTERVAL: Duration = Duration::from_secs(1);
+/// A slot that hasn't sent anything for this long is treated as unplugged
+const SLOT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const SLOTS: usize = 4;
+
+// Pad data button bits: first byte, then second byte. The face buttons are
+// named by position, so 0x80 (left face button) is Xbox X.
+const BUTTON_BITS: [(usize, u8, Button); 14] = [
+    (0, 0x80, Button::DpadLeft),
+    (0, 0x40, Button::DpadDown),
+    (0, 0x20, Button::DpadRight),
+    (0, 0x10, Button::DpadUp),
+    (0, 0x08, Button::Start),
+    (0, 0x04, Button::RightStick),
+    (0, 0x02, Button::LeftStick),
+    (0, 0x01, Button::Back),
+    (1, 0x80, Button::X),
+    (1, 0x40, Button::A),
+    (1, 0x20, Button::B),
+    (1, 0x10, Button::Y),
+    (1, 0x08, Button::RightShoulder),
+    (1, 0x04, Button::LeftShoulder),
+];
+
+/// CRC-32 (IEEE), as used by the DSU header.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn f32_at(data: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Wraps a client message (type followed by `body`) in a DSU header.
+fn build_packet(client_id: u32, message: u32, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + 4 + body.len());
+    packet.extend_from_slice(b"DSUC");
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    packet.extend_from_slice(&((4 + body.len()) as u16).to_le_bytes());
+    packet.extend_from_slice(&[0; 4]);
+    packet.extend_from_slice(&client_id.to_le_bytes());
+    packet.extend_from_slice(&message.to_le_bytes());
+    packet.extend_from_slice(body);
+
+    let crc = crc32(&packet);
+    packet[8..12].copy_from_slice(&crc.to_le_bytes());
+    packet
+}
+
+/// Checks the header of a server packet and returns its message type and the
+/// payload after it.
+fn parse_packet(data: &[u8]) -> Option<(u32, &[u8])> {
+    if data.len() < HEADER_LEN + 4 || &data[0..4] != b"DSUS" {
+        return None;
+    }
+    let len = u16::from_le_bytes([data[6], data[7]]) as usize;
+    let end = HEADER_LEN + len;
+    if data.len() < end {
+        return None;
+    }
+
+    let mut zeroed = data[..end].to_vec();
+    zeroed[8..12].fill(0);
+    if crc32(&zeroed) != u32_at(data, 8) {
+        return None;
+    }
+    Some((u32_at(data, HEADER_LEN), &data[HEADER_LEN + 4..end]))
+}
+
+/// Converts a 0..=255 stick byte (128 centered, up/right positive) to the XInput range.
+fn stick_axis(value: u8) -> f32 {
+    ((value as f32 - 128.0) / 127.0 * 32767.0).clamp(-32768.0, 32767.0)
+}
+
+/// Decodes a pad data payload (everything after the message type).
+/// Returns the slot, whether it's connected, and its state.
+fn parse_pad_data(data: &[u8]) -> Option<(usize, bool, GamepadState)> {
+    if data.len() < 80 {
+        return None;
+    }
+    // Shared with port info: slot, slot state, device model, connection type, MAC, battery
+    let slot = data[0] as usize;
+    let connected = data[1] == 2 && data[11] != 0;
+    let full_gyro = data[2] == 2;
+
+    let mut buttons = Buttons::default();
+    for (byte, mask, button) in BUTTON_BITS {
+        buttons.set(button, data[16 + byte] & mask != 0);
+    }
+
+    // Gyro is pitch, yaw, roll in deg/s after the timestamp and accelerometer
+    let motion = full_gyro.then(|| Motion { pitch: f32_at(data, 68), yaw: f32_at(data, 72) });
+
+    let state = GamepadState {
+        left_stick: (stick_axis(data[20]), stick_axis(data[21])),
+        right_stick: (stick_axis(data[22]), stick_axis(data[23])),
+        left_trigger: data[35] as f32 / 255.0,
+        right_trigger: data[34] as f32 / 255.0,
+        buttons,
+        motion,
+        touchpad: None,
+    };
+    Some((slot, connected, state))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    state: GamepadState,
+    received: Instant,
+}
+
+/// Socket and latest pad data per slot, shared by the backend and its devices.
+struct DsuClient {
+    socket: UdpSocket,
+    client_id: u32,
+    slots: [Option<Slot>; SLOTS],
+    last_subscribe: Option<Instant>,
+}
+
+impl DsuClient {
+    fn connect(host: &str, port: u16) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect((host, port))?;
+        socket.set_nonblocking(true)?;
+
+        // Any id works as long as it stays the same for the session
+        let client_id = std::process::id();
+        let client = Self { socket, client_id, slots: [None; SLOTS], last_subscribe: None };
+        client.send(MSG_VERSION, &[]);
+        Ok(client)
+    }
+
+    fn send(&self, message: u32, body: &[u8]) {
+        // The server may not be up yet; requests are repeated anyway
+        let _ = self.socket.send(&build_packet(self.client_id, message, body));
+    }
+
+    /// Renews the pad data subscription and applies everything received since the last call.
+    fn pump(&mut self) {
+        let now = Instant::now();
+        if self.last_subscribe.is_none_or(|t| now.duration_since(t) >= SUBSCRIBE_INTERVAL) {
+            self.last_subscribe = Some(now);
+            // Flags 0 subscribes to every slot; slot and MAC are then ignored
+            self.send(MSG_PAD_DATA, &[0; 8]);
+        }
+
+        let mut buf = [0u8; 1024];
+        // Stops at WouldBlock once drained; Windows also reports ICMP port
+        // unreachable here while no server is listening
+        while let Ok(len) = self.socket.recv(&mut buf) {
+            let Some((message, data)) = parse_packet(&buf[..len]) else {
+                continue;
+            };
+
+            match message {
+                MSG_VERSION if data.len() >= 2 => {
+                    println!("DSU server protocol version {}", u16::from_le_bytes([data[0], data[1]]));
+                }
+                MSG_PORT_INFO if data.len() >= 2 => {
+                    let slot = data[0] as usize;
+                    if slot < SLOTS && data[1] != 2 {
+                        self.slots[slot] = None;
+                    }
+                }
+                MSG_PAD_DATA => {
+                    if let Some((slot, connected, state)) = parse_pad_data(data) {
+                        if slot < SLOTS {
+                            self.slots[slot] = connected.then_some(Slot { state, received: now });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for slot in &mut self.slots {
+            if slot.is_some_and(|s| now.duration_since(s.received) > SLOT_TIMEOUT) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+pub struct DsuBackend {
+    client: Rc<RefCell<DsuClient>>,
+}
+
+impl DsuBackend {
+    pub fn new(host: &str, port: u16) -> Option<Self> {
+        match DsuClient::connect(host, port) {
+            Ok(client) => Some(Self { client: Rc::new(RefCell::new(client)) }),
+            Err(e) => {
+                println!("Failed to open DSU client for {}:{}: {}", host, port, e);
+                None
+            }
+        }
+    }
+}
+
+impl InputBackend for DsuBackend {
+    fn enumerate(&mut self) -> Vec<DeviceInfo> {
+        let mut client = self.client.borrow_mut();
+        // Answers arrive on a later call; until then slots show up from pad data
+        client.send(MSG_PORT_INFO, &[SLOTS as u8, 0, 0, 0, 0, 1, 2, 3]);
+        client.pump();
+
+        (0..SLOTS)
+            .filter(|&i| client.slots[i].is_some())
+            .map(|i| DeviceInfo {
+                id: DeviceId::Slot(i as u32),
+                name: format!("DSU slot {}", i),
+                key: format!("dsu:{}", i),
+            })
+            .collect()
+    }
+
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+        match info.id {
+            DeviceId::Slot(slot) if (slot as usize) < SLOTS => Some(Box::new(DsuDevice {
+                client: self.client.clone(),
+                slot: slot as usize,
+            })),
+            _ => None,
+        }
+    }
+}
+
+struct DsuDevice {
+    client: Rc<RefCell<DsuClient>>,
+    slot: usize,
+}
+
+impl GamepadDevice for DsuDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        let mut client = self.client.borrow_mut();
+        client.pump();
+        client.slots[self.slot].map(|s| s.state).ok_or(PollError::Disconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server packet: the same header as `build_packet` with the server magic.
+    fn server_packet(message: u32, body: &[u8]) -> Vec<u8> {
+        let mut packet = build_packet(0, message, body);
+        packet[0..4].copy_from_slice(b"DSUS");
+        packet[8..12].fill(0);
+        let crc = crc32(&packet);
+        packet[8..12].copy_from_slice(&crc.to_le_bytes());
+        packet
+    }
+
+    /// Pad data for a connected controller with full gyro in `slot`, holding A
+    /// with the left stick full right, the right trigger half pulled and
+    /// turning at 90 deg/s of yaw.
+    fn pad_data(slot: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 80];
+        data[0] = slot;
+        data[1] = 2;
+        data[2] = 2;
+        data[11] = 1;
+        data[17] = 0x40;
+        data[20..24].copy_from_slice(&[255, 128, 128, 128]);
+        data[34] = 128;
+        data[72..76].copy_from_slice(&90.0f32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn crc_matches_the_ieee_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn packets_with_a_bad_crc_are_dropped() {
+        let mut packet = server_packet(MSG_PAD_DATA, &pad_data(0));
+        assert!(parse_packet(&packet).is_some());
+        packet[HEADER_LEN + 10] ^= 1;
+        assert!(parse_packet(&packet).is_none());
+    }
+
+    #[test]
+    fn pad_data_decodes_sticks_buttons_triggers_and_gyro() {
+        let (slot, connected, state) = parse_pad_data(&pad_data(2)).unwrap();
+        assert_eq!(slot, 2);
+        assert!(connected);
+        assert_eq!(state.left_stick, (32767.0, 0.0));
+        assert!(state.buttons.contains(Button::A));
+        assert!(!state.buttons.contains(Button::X));
+        assert!((state.right_trigger - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!(state.left_trigger, 0.0);
+        assert_eq!(state.motion.map(|m| m.yaw), Some(90.0));
+    }
+
+    #[test]
+    fn reads_pad_data_from_a_server_after_subscribing() {
+        let server = UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let mut backend = DsuBackend::new("127.0.0.1", server.local_addr().unwrap().port()).unwrap();
+
+        // Sends the subscription along with the version and port info requests
+        assert!(backend.enumerate().is_empty());
+        let mut buf = [0u8; 1024];
+        loop {
+            let (len, client) = server.recv_from(&mut buf).expect("the server never saw a subscription");
+            let request = &buf[..len];
+            assert_eq!(&request[0..4], b"DSUC");
+            if u32_at(request, HEADER_LEN) == MSG_PAD_DATA {
+                server.send_to(&server_packet(MSG_PAD_DATA, &pad_data(1)), client).unwrap();
+                break;
+            }
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let devices = loop {
+            let devices = backend.enumerate();
+            if !devices.is_empty() || Instant::now() > deadline {
+                break devices;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].key, "dsu:1");
+        let state = backend.open(&devices[0]).unwrap().poll().unwrap();
+        assert_eq!(state.left_stick, (32767.0, 0.0));
+        assert!(state.buttons.contains(Button::A));
+    }
+}
@@ -0,0 +1,413 @@
+//! Linux evdev backend. Joysticks are read straight from `/dev/input/event*`.
+//!
+//! Setting `CONTROLLER_TRAY_REPLAY` to a recording makes the backend expose
+//! that file as its only device instead, one frame per poll. Recordings are
+//! plain text:
+//!
+//! ```text
+//! # axis <code> <min> <max> <flat>
+//! axis 0 -32768 32767 128
+//! axis 1 -32768 32767 128
+//! # <time> <type> <code> <value>, frames end at SYN_REPORT (0 0 0)
+//! 0.000000 3 0 12000
+//! 0.000000 0 0 0
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use evdev::{AbsoluteAxisType, Device, Key};
+
+use super::{Button, Buttons, DeviceId, DeviceInfo, GamepadDevice, GamepadState, InputBackend, PollError};
+
+const REPLAY_ENV: &str = "CONTROLLER_TRAY_REPLAY";
+
+const EV_SYN: u16 = 0;
+const EV_KEY: u16 = 1;
+const EV_ABS: u16 = 3;
+
+// Axes read from the device, in the order they're stored in `PadState`.
+// Xbox-style drivers report the triggers on Z and RZ.
+const AXES: [AbsoluteAxisType; 6] = [
+    AbsoluteAxisType::ABS_X,
+    AbsoluteAxisType::ABS_Y,
+    AbsoluteAxisType::ABS_RX,
+    AbsoluteAxisType::ABS_RY,
+    AbsoluteAxisType::ABS_Z,
+    AbsoluteAxisType::ABS_RZ,
+];
+
+// Linux gamepad drivers name face buttons by position: BTN_NORTH is Xbox X, BTN_WEST is Y
+const KEY_MAP: [(Key, Button); 14] = [
+    (Key::BTN_SOUTH, Button::A),
+    (Key::BTN_EAST, Button::B),
+    (Key::BTN_NORTH, Button::X),
+    (Key::BTN_WEST, Button::Y),
+    (Key::BTN_TL, Button::LeftShoulder),
+    (Key::BTN_TR, Button::RightShoulder),
+    (Key::BTN_SELECT, Button::Back),
+    (Key::BTN_START, Button::Start),
+    (Key::BTN_THUMBL, Button::LeftStick),
+    (Key::BTN_THUMBR, Button::RightStick),
+    (Key::BTN_DPAD_UP, Button::DpadUp),
+    (Key::BTN_DPAD_DOWN, Button::DpadDown),
+    (Key::BTN_DPAD_LEFT, Button::DpadLeft),
+    (Key::BTN_DPAD_RIGHT, Button::DpadRight),
+];
+
+/// Range and flat zone the kernel reports for an absolute axis.
+#[derive(Debug, Clone, Copy)]
+struct AxisRange {
+    min: i32,
+    max: i32,
+    flat: i32,
+}
+
+impl AxisRange {
+    /// Maps a raw value into the XInput range, zeroing anything inside the flat zone.
+    fn normalize(&self, value: i32) -> f32 {
+        let center = (self.min as f32 + self.max as f32) / 2.0;
+        let half = (self.max as f32 - self.min as f32) / 2.0;
+        if half <= 0.0 {
+            return 0.0;
+        }
+
+        let offset = value as f32 - center;
+        if offset.abs() <= self.flat as f32 {
+            return 0.0;
+        }
+        (offset / half * 32767.0).clamp(-32768.0, 32767.0)
+    }
+
+    /// Maps a trigger value to 0..=1 from the bottom of its range.
+    fn fraction(&self, value: i32) -> f32 {
+        let span = self.max as f32 - self.min as f32;
+        if span <= 0.0 || value - self.min <= self.flat {
+            return 0.0;
+        }
+        ((value - self.min) as f32 / span).clamp(0.0, 1.0)
+    }
+}
+
+/// Last known value of each axis, with the range used to normalise it,
+/// plus buttons and the hat most pads report the D-pad on.
+struct PadState {
+    ranges: [Option<AxisRange>; 6],
+    values: [i32; 6],
+    hat: (i32, i32),
+    keys: Buttons,
+}
+
+impl PadState {
+    fn new(ranges: [Option<AxisRange>; 6]) -> Self {
+        // Start the sticks centered and the triggers (Z, RZ) released, so an
+        // axis that hasn't reported yet reads as 0
+        let values: [i32; 6] = std::array::from_fn(|i| {
+            ranges[i].map_or(0, |r| if i < 4 { r.min + (r.max - r.min) / 2 } else { r.min })
+        });
+        Self { ranges, values, hat: (0, 0), keys: Buttons::default() }
+    }
+
+    fn set(&mut self, code: u16, value: i32) {
+        if let Some(i) = AXES.iter().position(|axis| axis.0 == code) {
+            self.values[i] = value;
+        } else if code == AbsoluteAxisType::ABS_HAT0X.0 {
+            self.hat.0 = value;
+        } else if code == AbsoluteAxisType::ABS_HAT0Y.0 {
+            self.hat.1 = value;
+        }
+    }
+
+    fn set_key(&mut self, code: u16, pressed: bool) {
+        if let Some((_, button)) = KEY_MAP.iter().find(|(key, _)| key.code() == code) {
+            self.keys.set(*button, pressed);
+        }
+    }
+
+    fn axis(&self, i: usize) -> f32 {
+        self.ranges[i].map_or(0.0, |r| r.normalize(self.values[i]))
+    }
+
+    fn trigger(&self, i: usize) -> f32 {
+        self.ranges[i].map_or(0.0, |r| r.fraction(self.values[i]))
+    }
+
+    fn state(&self) -> GamepadState {
+        let mut buttons = self.keys;
+        buttons.set(Button::DpadUp, buttons.contains(Button::DpadUp) || self.hat.1 < 0);
+        buttons.set(Button::DpadDown, buttons.contains(Button::DpadDown) || self.hat.1 > 0);
+        buttons.set(Button::DpadLeft, buttons.contains(Button::DpadLeft) || self.hat.0 < 0);
+        buttons.set(Button::DpadRight, buttons.contains(Button::DpadRight) || self.hat.0 > 0);
+
+        // evdev reports Y growing downwards, XInput has up as positive
+        GamepadState {
+            left_stick: (self.axis(0), -self.axis(1)),
+            right_stick: (self.axis(2), -self.axis(3)),
+            left_trigger: self.trigger(4),
+            right_trigger: self.trigger(5),
+            buttons,
+            motion: None,
+            touchpad: None,
+        }
+    }
+}
+
+fn is_joystick(device: &Device) -> bool {
+    let has_sticks = device.supported_absolute_axes().is_some_and(|axes| {
+        axes.contains(AbsoluteAxisType::ABS_X) && axes.contains(AbsoluteAxisType::ABS_Y)
+    });
+    // Touchpads and tablets also report ABS_X/ABS_Y, only take devices with gamepad buttons
+    let has_buttons = device.supported_keys().is_some_and(|keys| {
+        keys.contains(Key::BTN_SOUTH) || keys.contains(Key::BTN_TRIGGER)
+    });
+    has_sticks && has_buttons
+}
+
+/// Event node numbers are handed out in plug-in order, so identify the
+/// controller by VID/PID plus its serial, or the port it's plugged into when
+/// it doesn't report one.
+fn device_key(device: &Device) -> String {
+    let id = device.input_id();
+    let location = device
+        .unique_name()
+        .filter(|uniq| !uniq.is_empty())
+        .or(device.physical_path())
+        .unwrap_or("");
+    format!("evdev:{:04x}:{:04x}:{}", id.vendor(), id.product(), location)
+}
+
+pub struct EvdevBackend {
+    replay: Option<PathBuf>,
+}
+
+impl EvdevBackend {
+    pub fn new() -> Self {
+        Self {
+            replay: std::env::var_os(REPLAY_ENV).map(PathBuf::from),
+        }
+    }
+}
+
+impl InputBackend for EvdevBackend {
+    fn enumerate(&mut self) -> Vec<DeviceInfo> {
+        if let Some(path) = &self.replay {
+            return vec![DeviceInfo {
+                id: DeviceId::Path(path.clone()),
+                name: format!("Replay of {}", path.display()),
+                key: format!("replay:{}", path.display()),
+            }];
+        }
+
+        let mut devices: Vec<DeviceInfo> = evdev::enumerate()
+            .filter(|(_, device)| is_joystick(device))
+            .map(|(path, device)| DeviceInfo {
+                name: device.name().unwrap_or("Unknown joystick").to_string(),
+                key: device_key(&device),
+                id: DeviceId::Path(path),
+            })
+            .collect();
+        // read_dir order is arbitrary, keep event0 before event1 between runs
+        devices.sort_by(|a, b| match (&a.id, &b.id) {
+            (DeviceId::Path(a), DeviceId::Path(b)) => a.cmp(b),
+            _ => std::cmp::Ordering::Equal,
+        });
+        devices
+    }
+
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+        let DeviceId::Path(path) = &info.id else {
+            return None;
+        };
+
+        if self.replay.as_ref() == Some(path) {
+            return match ReplayDevice::load(path) {
+                Ok(device) => Some(Box::new(device)),
+                Err(e) => {
+                    println!("Failed to load replay {}: {}", path.display(), e);
+                    None
+                }
+            };
+        }
+
+        match EvdevDevice::open(path) {
+            Ok(device) => Some(Box::new(device)),
+            Err(e) => {
+                println!("Failed to open {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+struct EvdevDevice {
+    device: Device,
+    pad: PadState,
+}
+
+impl EvdevDevice {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let device = Device::open(path)?;
+        let abs = device.get_abs_state()?;
+        let supported = device.supported_absolute_axes();
+        let ranges = AXES.map(|axis| {
+            let info = &abs[axis.0 as usize];
+            supported
+                .filter(|s| s.contains(axis))
+                .map(|_| AxisRange { min: info.minimum, max: info.maximum, flat: info.flat })
+        });
+        Ok(Self { device, pad: PadState::new(ranges) })
+    }
+}
+
+impl GamepadDevice for EvdevDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        // EVIOCGABS gives the current value without draining the event queue;
+        // it fails with ENODEV once the controller is unplugged
+        let abs = self.device.get_abs_state().map_err(|_| PollError::Disconnected)?;
+        let keys = self.device.get_key_state().map_err(|_| PollError::Disconnected)?;
+
+        for axis in AXES.iter().chain(&[AbsoluteAxisType::ABS_HAT0X, AbsoluteAxisType::ABS_HAT0Y]) {
+            self.pad.set(axis.0, abs[axis.0 as usize].value);
+        }
+        for (key, _) in KEY_MAP {
+            self.pad.set_key(key.code(), keys.contains(key));
+        }
+        Ok(self.pad.state())
+    }
+}
+
+/// Plays back a recording made from a real device, one SYN_REPORT frame per poll.
+struct ReplayDevice {
+    pad: PadState,
+    frames: Vec<Vec<(u16, u16, i32)>>,
+    next: usize,
+}
+
+impl ReplayDevice {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut ranges = [None; 6];
+        let mut frames = Vec::new();
+        let mut frame = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let bad_line = || anyhow::anyhow!("line {}: can't parse {:?}", n + 1, line);
+
+            if fields[0] == "axis" {
+                let [code, min, max, flat] = fields[1..] else {
+                    return Err(bad_line());
+                };
+                let code: u16 = code.parse().map_err(|_| bad_line())?;
+                let range = AxisRange {
+                    min: min.parse().map_err(|_| bad_line())?,
+                    max: max.parse().map_err(|_| bad_line())?,
+                    flat: flat.parse().map_err(|_| bad_line())?,
+                };
+                if let Some(i) = AXES.iter().position(|axis| axis.0 == code) {
+                    ranges[i] = Some(range);
+                }
+                continue;
+            }
+
+            let [_time, kind, code, value] = fields[..] else {
+                return Err(bad_line());
+            };
+            let kind: u16 = kind.parse().map_err(|_| bad_line())?;
+            let code: u16 = code.parse().map_err(|_| bad_line())?;
+            let value: i32 = value.parse().map_err(|_| bad_line())?;
+
+            if kind == EV_SYN && code == 0 {
+                frames.push(std::mem::take(&mut frame));
+            } else {
+                frame.push((kind, code, value));
+            }
+        }
+
+        if !frame.is_empty() {
+            frames.push(frame);
+        }
+
+        Ok(Self { pad: PadState::new(ranges), frames, next: 0 })
+    }
+}
+
+impl GamepadDevice for ReplayDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        // Report the end of the recording like an unplug
+        let frame = self.frames.get(self.next).ok_or(PollError::Disconnected)?;
+        self.next += 1;
+
+        for &(kind, code, value) in frame {
+            match kind {
+                EV_ABS => self.pad.set(code, value),
+                EV_KEY => self.pad.set_key(code, value != 0),
+                _ => {}
+            }
+        }
+        Ok(self.pad.state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/evdev-xpad.txt")
+    }
+
+    #[test]
+    fn replay_plays_one_frame_per_poll() {
+        let mut device = ReplayDevice::load(&fixture()).unwrap();
+
+        // Resting noise is inside the flat zone
+        assert_eq!(device.poll(), Ok(GamepadState::default()));
+
+        let right = device.poll().unwrap();
+        assert_eq!(right.left_stick, (32767.0, 0.0));
+
+        // Y is flipped to up-positive
+        let up = device.poll().unwrap();
+        assert_eq!(up.left_stick, (0.0, 32767.0));
+
+        let pressed = device.poll().unwrap();
+        assert!(pressed.buttons.contains(Button::A));
+        assert_eq!(pressed.right_trigger, 1.0);
+        assert_eq!(pressed.left_trigger, 0.0);
+        assert_eq!(pressed.left_stick, (0.0, 0.0));
+
+        let released = device.poll().unwrap();
+        assert!(!released.buttons.contains(Button::A));
+        assert!(released.buttons.contains(Button::DpadUp));
+        assert_eq!(released.right_trigger, 0.0);
+
+        assert_eq!(device.poll(), Err(PollError::Disconnected));
+    }
+
+    #[test]
+    fn backend_exposes_the_replay_as_its_only_device() {
+        let mut backend = EvdevBackend { replay: Some(fixture()) };
+        let devices = backend.enumerate();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].key, format!("replay:{}", fixture().display()));
+
+        let mut device = backend.open(&devices[0]).unwrap();
+        assert_eq!(device.poll(), Ok(GamepadState::default()));
+    }
+
+    #[test]
+    fn replay_rejects_malformed_lines() {
+        let error = ReplayDevice::parse("axis 0 -32768 32767\n").err().unwrap();
+        assert!(error.to_string().starts_with("line 1:"), "{}", error);
+        assert!(ReplayDevice::parse("0.0 3 zero 5\n").is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! Legion Go detachable controllers, read as raw HID reports from the
+//! controllers' vendor interface (usage page 0xFFA0) through `/dev/hidraw*`.
+//!
+//! Both controllers arrive in one 64 byte input report (report ID 0x04),
+//! whether they're attached to the console or not. Each is still listed as a
+//! device of its own, so they can be picked and calibrated separately; both
+//! read the same hidraw node. The layout below follows
+//! community reverse-engineering of the report, not a published spec; setting
+//! `CONTROLLER_TRAY_HID_DUMP=1` prints every report to help check it against
+//! new firmware.
+
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use super::{Button, Buttons, DeviceId, DeviceInfo, GamepadDevice, GamepadState, InputBackend, Motion, PollError};
+
+const VENDOR_LENOVO: u32 = 0x17EF;
+// One product id per controller mode (XInput, DirectInput, FPS, ...)
+const PRODUCT_IDS: [u32; 4] = [0x6182, 0x6183, 0x6184, 0x6185];
+// `Usage Page (0xFFA0)` as it appears in the report descriptor
+const VENDOR_USAGE_PAGE: [u8; 3] = [0x06, 0xA0, 0xFF];
+
+const DUMP_ENV: &str = "CONTROLLER_TRAY_HID_DUMP";
+// O_NONBLOCK on Linux, std doesn't export it
+const O_NONBLOCK: i32 = 0o4000;
+
+const REPORT_ID: u8 = 0x04;
+const REPORT_LEN: usize = 64;
+
+// Byte offsets into the report
+const LEFT_CONNECTED: usize = 1;
+const RIGHT_CONNECTED: usize = 2;
+const LEFT_STICK: usize = 14; // x, y: u8, 128 centered, y down
+const RIGHT_STICK: usize = 16;
+const LEFT_TRIGGER: usize = 22; // u8
+const RIGHT_TRIGGER: usize = 23;
+const TOUCHPAD: usize = 25; // x, y: u16 big endian, 0..=1000, both 0 when not touched
+const LEFT_GYRO: usize = 34; // x, y, z: i16 little endian
+const RIGHT_GYRO: usize = 40;
+
+/// Gyro LSBs per deg/s (±2000 deg/s full scale)
+const GYRO_SCALE: f32 = 16.4;
+
+/// One of the two detachable controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    // Right first, so it's stick 1 when nothing is pinned and its gyro wins
+    pub const ALL: [Side; 2] = [Side::Right, Side::Left];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Side::Left => "Left",
+            Side::Right => "Right",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Side::Left => "legion-go:left",
+            Side::Right => "legion-go:right",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Side::ALL.into_iter().find(|side| side.key() == key)
+    }
+}
+
+// (byte, mask, side, button). Bytes 18 to 20 hold the standard buttons; the
+// Legion L/R buttons and back paddles have no `Button` and are left out.
+// View and Menu are given to the left and right sides as on an Xbox pad.
+const BUTTON_BITS: [(usize, u8, Side, Button); 14] = [
+    (18, 0x20, Side::Left, Button::LeftStick),
+    (18, 0x10, Side::Right, Button::RightStick),
+    (18, 0x08, Side::Left, Button::DpadUp),
+    (18, 0x04, Side::Left, Button::DpadDown),
+    (18, 0x02, Side::Left, Button::DpadLeft),
+    (18, 0x01, Side::Left, Button::DpadRight),
+    (19, 0x80, Side::Right, Button::A),
+    (19, 0x40, Side::Right, Button::B),
+    (19, 0x20, Side::Right, Button::X),
+    (19, 0x10, Side::Right, Button::Y),
+    (19, 0x08, Side::Left, Button::LeftShoulder),
+    (19, 0x02, Side::Right, Button::RightShoulder),
+    (20, 0x80, Side::Left, Button::Back),
+    (20, 0x40, Side::Right, Button::Start),
+];
+
+/// Both controllers as decoded from one input report, `None` for one that's
+/// switched off. Each only fills in its own half of `GamepadState`: the left
+/// controller its left stick, trigger and buttons, the right one the others
+/// and the touchpad.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LegionReport {
+    pub left: Option<GamepadState>,
+    pub right: Option<GamepadState>,
+}
+
+impl LegionReport {
+    pub fn side(&self, side: Side) -> Option<GamepadState> {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+}
+
+fn stick_axis(value: u8) -> f32 {
+    ((value as f32 - 128.0) / 127.0 * 32767.0).clamp(-32768.0, 32767.0)
+}
+
+fn gyro_at(report: &[u8], offset: usize) -> Motion {
+    let axis = |i: usize| i16::from_le_bytes([report[offset + i * 2], report[offset + i * 2 + 1]]) as f32 / GYRO_SCALE;
+    Motion { pitch: axis(0), yaw: -axis(1) }
+}
+
+/// Decodes a raw input report, or `None` if it isn't a controller report.
+pub fn parse_report(report: &[u8]) -> Option<LegionReport> {
+    if report.len() < REPORT_LEN || report[0] != REPORT_ID {
+        return None;
+    }
+
+    let buttons = |side: Side| {
+        let mut buttons = Buttons::default();
+        for (byte, mask, _, button) in BUTTON_BITS.into_iter().filter(|bit| bit.2 == side) {
+            buttons.set(button, report[byte] & mask != 0);
+        }
+        buttons
+    };
+    let stick = |offset: usize| (stick_axis(report[offset]), -stick_axis(report[offset + 1]));
+
+    let touch_x = u16::from_be_bytes([report[TOUCHPAD], report[TOUCHPAD + 1]]);
+    let touch_y = u16::from_be_bytes([report[TOUCHPAD + 2], report[TOUCHPAD + 3]]);
+    let touchpad = (touch_x != 0 || touch_y != 0)
+        .then(|| (touch_x.min(1000) as f32 / 1000.0, touch_y.min(1000) as f32 / 1000.0));
+
+    let left = GamepadState {
+        left_stick: stick(LEFT_STICK),
+        left_trigger: report[LEFT_TRIGGER] as f32 / 255.0,
+        buttons: buttons(Side::Left),
+        motion: Some(gyro_at(report, LEFT_GYRO)),
+        ..Default::default()
+    };
+    let right = GamepadState {
+        right_stick: stick(RIGHT_STICK),
+        right_trigger: report[RIGHT_TRIGGER] as f32 / 255.0,
+        buttons: buttons(Side::Right),
+        motion: Some(gyro_at(report, RIGHT_GYRO)),
+        // The touchpad is on the right controller
+        touchpad,
+        ..Default::default()
+    };
+
+    Some(LegionReport {
+        left: (report[LEFT_CONNECTED] != 0).then_some(left),
+        right: (report[RIGHT_CONNECTED] != 0).then_some(right),
+    })
+}
+
+/// Reads `HID_ID=0003:000017EF:00006182` from a hidraw node's uevent.
+fn hid_ids(uevent: &str) -> Option<(u32, u32)> {
+    let id = uevent.lines().find_map(|line| line.strip_prefix("HID_ID="))?;
+    let mut parts = id.split(':').skip(1);
+    let vendor = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let product = u32::from_str_radix(parts.next()?, 16).ok()?;
+    Some((vendor, product))
+}
+
+/// True for the hidraw node of the controllers' vendor interface; the same
+/// device also exposes keyboard and mouse interfaces.
+fn is_controller_interface(sys: &Path) -> bool {
+    let Ok(uevent) = fs::read_to_string(sys.join("device/uevent")) else {
+        return false;
+    };
+    let Some((vendor, product)) = hid_ids(&uevent) else {
+        return false;
+    };
+    if vendor != VENDOR_LENOVO || !PRODUCT_IDS.contains(&product) {
+        return false;
+    }
+    fs::read(sys.join("device/report_descriptor"))
+        .is_ok_and(|desc| desc.windows(VENDOR_USAGE_PAGE.len()).any(|w| w == VENDOR_USAGE_PAGE))
+}
+
+pub struct LegionBackend;
+
+impl InputBackend for LegionBackend {
+    fn enumerate(&mut self) -> Vec<DeviceInfo> {
+        let Ok(entries) = fs::read_dir("/sys/class/hidraw") else {
+            return Vec::new();
+        };
+        let mut nodes: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| is_controller_interface(&entry.path()))
+            .map(|entry| Path::new("/dev").join(entry.file_name()))
+            .collect();
+        nodes.sort();
+        // There's only ever the one receiver, so the side alone is a stable key
+        nodes
+            .into_iter()
+            .flat_map(|node| {
+                Side::ALL.map(|side| DeviceInfo {
+                    id: DeviceId::Path(node.clone()),
+                    name: format!("Legion Go {} controller", side.label().to_lowercase()),
+                    key: side.key().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+        let (DeviceId::Path(path), Some(side)) = (&info.id, Side::from_key(&info.key)) else {
+            return None;
+        };
+        // Each side opens the node on its own, hidraw hands every reader a copy of each report
+        match fs::OpenOptions::new().read(true).custom_flags(O_NONBLOCK).open(path) {
+            Ok(file) => Some(Box::new(LegionDevice {
+                file,
+                path: path.clone(),
+                side,
+                last: None,
+                dump: std::env::var_os(DUMP_ENV).is_some(),
+            })),
+            Err(e) => {
+                println!("Failed to open {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+struct LegionDevice {
+    file: File,
+    path: PathBuf,
+    side: Side,
+    last: Option<LegionReport>,
+    dump: bool,
+}
+
+impl LegionDevice {
+    fn log_connection(&self, report: &LegionReport) {
+        let was = self.last.is_some_and(|r| r.side(self.side).is_some());
+        let now = report.side(self.side).is_some();
+        if was != now {
+            println!("Legion Go {} controller {}", self.side.label(), if now { "connected" } else { "disconnected" });
+        }
+    }
+}
+
+impl GamepadDevice for LegionDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        // Drain everything queued since the last poll, the newest report wins
+        let mut buf = [0u8; REPORT_LEN];
+        loop {
+            match self.file.read(&mut buf) {
+                // hidraw doesn't end the stream, but don't spin if it ever does
+                Ok(0) => break,
+                Ok(len) => {
+                    if self.dump {
+                        println!("{}: {:02x?}", self.path.display(), &buf[..len]);
+                    }
+                    if let Some(report) = parse_report(&buf[..len]) {
+                        self.log_connection(&report);
+                        self.last = Some(report);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // ENODEV once the receiver is unplugged
+                Err(_) => return Err(PollError::Disconnected),
+            }
+        }
+
+        // Nothing yet, or this controller is switched off
+        Ok(self.last.and_then(|report| report.side(self.side)).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The reports in the fixture, one per `CONTROLLER_TRAY_HID_DUMP` line.
+    fn fixture() -> Vec<Vec<u8>> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/legion-go-reports.txt");
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let bytes = line.split_once(": [").unwrap().1.trim_end_matches(']');
+                bytes.split(", ").map(|byte| u8::from_str_radix(byte, 16).unwrap()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resting_report_has_both_controllers_centered() {
+        let report = parse_report(&fixture()[0]).unwrap();
+        let left = report.left.unwrap();
+        let right = report.right.unwrap();
+        assert_eq!(left.left_stick, (0.0, 0.0));
+        assert_eq!(right.right_stick, (0.0, 0.0));
+        assert_eq!(left.buttons, Buttons::default());
+        assert_eq!(right.buttons, Buttons::default());
+        assert_eq!(right.motion, Some(Motion { pitch: 0.0, yaw: 0.0 }));
+        assert_eq!(right.touchpad, None);
+    }
+
+    #[test]
+    fn each_side_only_reports_its_own_controls() {
+        let report = parse_report(&fixture()[1]).unwrap();
+        let left = report.left.unwrap();
+        let right = report.right.unwrap();
+
+        assert_eq!(left.left_stick, (32767.0, 0.0));
+        assert_eq!(left.right_trigger, 0.0);
+        assert!(!left.buttons.contains(Button::A));
+        assert_eq!(left.motion, Some(Motion { pitch: 0.0, yaw: 0.0 }));
+
+        assert_eq!(right.left_stick, (0.0, 0.0));
+        assert_eq!(right.right_trigger, 1.0);
+        assert!(right.buttons.contains(Button::A));
+        let yaw = right.motion.unwrap().yaw;
+        assert!((yaw - 100.0).abs() < 0.01, "{}", yaw);
+
+        assert_eq!(right.touchpad, Some((0.5, 0.25)));
+        assert_eq!(left.touchpad, None);
+    }
+
+    #[test]
+    fn switched_off_controller_is_missing_from_the_report() {
+        let report = parse_report(&fixture()[2]).unwrap();
+        assert_eq!(report.right, None);
+        let left = report.left.unwrap();
+        assert!(left.buttons.contains(Button::DpadUp));
+        assert!(left.buttons.contains(Button::LeftShoulder));
+        let pitch = left.motion.unwrap().pitch;
+        assert!((pitch - 10.0).abs() < 0.01, "{}", pitch);
+    }
+
+    #[test]
+    fn other_reports_are_ignored() {
+        let reports = fixture();
+        assert_eq!(parse_report(&reports[3]), None);
+        assert_eq!(parse_report(&reports[0][..REPORT_LEN - 1]), None);
+    }
+
+    #[test]
+    fn keys_name_the_side() {
+        for side in Side::ALL {
+            assert_eq!(Side::from_key(side.key()), Some(side));
+        }
+        assert_eq!(Side::from_key("legion-go"), None);
+    }
+
+    #[test]
+    fn poll_keeps_the_newest_report_and_stops_at_the_end() {
+        // A plain file ends with Ok(0) where hidraw would block
+        let path = std::env::temp_dir().join(format!("legion-go-poll-{}.bin", std::process::id()));
+        fs::write(&path, fixture()[..3].concat()).unwrap();
+        let open = |side| LegionDevice { file: File::open(&path).unwrap(), path: path.clone(), side, last: None, dump: false };
+        let mut left = open(Side::Left);
+        let mut right = open(Side::Right);
+        fs::remove_file(&path).unwrap();
+
+        let state = left.poll().unwrap();
+        assert!(state.buttons.contains(Button::DpadUp));
+        assert_eq!(left.poll().unwrap(), state);
+        // Switched off in the newest report
+        assert_eq!(right.poll(), Ok(GamepadState::default()));
+    }
+
+    #[test]
+    fn reads_ids_from_uevent() {
+        let uevent = "DRIVER=hid-generic\nHID_ID=0003:000017EF:00006182\nHID_NAME=Legion Controller\n";
+        assert_eq!(hid_ids(uevent), Some((VENDOR_LENOVO, 0x6182)));
+        assert_eq!(hid_ids("DRIVER=hid-generic\n"), None);
+    }
+}
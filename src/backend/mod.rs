@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{InputType, Settings};
+
+#[cfg(windows)]
+mod xinput;
+#[cfg(windows)]
+mod dinput;
+#[cfg(target_os = "linux")]
+mod evdev;
+#[cfg(target_os = "linux")]
+mod legion;
+mod dsu;
+
+/// Controller buttons, named after the Xbox layout every backend is mapped onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Button {
+    A,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    Back,
+    Start,
+    LeftStick,
+    RightStick,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 14] = [
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::Back,
+        Button::Start,
+        Button::LeftStick,
+        Button::RightStick,
+        Button::DpadUp,
+        Button::DpadDown,
+        Button::DpadLeft,
+        Button::DpadRight,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Button::A => "A",
+            Button::B => "B",
+            Button::X => "X",
+            Button::Y => "Y",
+            Button::LeftShoulder => "LB",
+            Button::RightShoulder => "RB",
+            Button::Back => "Back",
+            Button::Start => "Start",
+            Button::LeftStick => "Left Stick Press",
+            Button::RightStick => "Right Stick Press",
+            Button::DpadUp => "D-pad Up",
+            Button::DpadDown => "D-pad Down",
+            Button::DpadLeft => "D-pad Left",
+            Button::DpadRight => "D-pad Right",
+        }
+    }
+}
+
+/// Set of pressed buttons, one bit per `Button`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons(u32);
+
+impl Buttons {
+    pub fn contains(self, button: Button) -> bool {
+        self.0 & (1 << button as u32) != 0
+    }
+
+    pub fn set(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.0 |= 1 << button as u32;
+        } else {
+            self.0 &= !(1 << button as u32);
+        }
+    }
+
+    /// Raw bitmask, for passing the set between processes.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        Buttons(bits)
+    }
+}
+
+impl std::ops::BitOr for Buttons {
+    type Output = Buttons;
+
+    fn bitor(self, rhs: Buttons) -> Buttons {
+        Buttons(self.0 | rhs.0)
+    }
+}
+
+/// Gyro angular velocity in degrees per second, with the controller held flat
+/// in front of you.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Motion {
+    /// Positive when the far edge tilts up
+    pub pitch: f32,
+    /// Positive when turning left
+    pub yaw: f32,
+}
+
+/// Snapshot of a controller, normalised so every backend feeds the same pipeline.
+/// Stick axes use the XInput range (-32768..=32767, centered at 0), triggers
+/// go from 0 (released) to 1 (fully pulled).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadState {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    pub buttons: Buttons,
+    /// `None` for controllers without a gyro, or backends that can't read it
+    pub motion: Option<Motion>,
+    /// Touch position, 0..=1 from the top left; `None` while nothing touches
+    /// it or for controllers without a touchpad
+    pub touchpad: Option<(f32, f32)>,
+}
+
+/// Backend-specific key used to reopen a device returned by `enumerate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceId {
+    /// XInput user index or DSU slot (0-3)
+    Slot(u32),
+    /// DirectInput instance GUID
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Guid(u128),
+    /// evdev node, e.g. /dev/input/event5
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub id: DeviceId,
+    pub name: String,
+    /// Identifies the physical controller across sessions and reconnects,
+    /// unlike `id` which can change with enumeration order. Stored in
+    /// config.toml to pin a controller to stick 1 or stick 2.
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollError {
+    /// The device is gone, drop it and enumerate again
+    Disconnected,
+    /// The read failed but the device may recover on a later poll
+    #[cfg_attr(not(windows), allow(dead_code))]
+    NotReady,
+}
+
+pub trait GamepadDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError>;
+}
+
+pub trait InputBackend {
+    /// Lists the devices that are currently attached.
+    fn enumerate(&mut self) -> Vec<DeviceInfo>;
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>>;
+}
+
+/// Number of XInput user indices, one per player light on the controller.
+pub const XINPUT_USERS: u32 = 4;
+
+/// `DeviceInfo::key` of the XInput controller at user index `index`.
+pub fn xinput_key(index: u32) -> String {
+    format!("xinput:{}", index)
+}
+
+/// Creates the backend for `settings.input_type`, or `None` if it can't be initialised on this system.
+pub fn open_backend(settings: &Settings) -> Option<Box<dyn InputBackend>> {
+    match settings.input_type {
+        #[cfg(windows)]
+        InputType::XInput => Some(Box::new(xinput::XInputBackend)),
+        #[cfg(windows)]
+        InputType::DirectInputSingle | InputType::DirectInputDual => {
+            dinput::DirectInputBackend::new().map(|b| Box::new(b) as Box<dyn InputBackend>)
+        }
+        #[cfg(target_os = "linux")]
+        InputType::Evdev => Some(Box::new(evdev::EvdevBackend::new())),
+        #[cfg(target_os = "linux")]
+        InputType::LegionGo => Some(Box::new(legion::LegionBackend)),
+        InputType::Dsu => {
+            dsu::DsuBackend::new(&settings.dsu_host, settings.dsu_port).map(|b| Box::new(b) as Box<dyn InputBackend>)
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
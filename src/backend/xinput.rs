@@ -0,0 +1,87 @@
+use windows::Win32::UI::Input::XboxController::{
+    XInputGetState, XINPUT_STATE, XINPUT_GAMEPAD, XINPUT_GAMEPAD_BUTTON_FLAGS,
+    XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y,
+    XINPUT_GAMEPAD_LEFT_SHOULDER, XINPUT_GAMEPAD_RIGHT_SHOULDER,
+    XINPUT_GAMEPAD_BACK, XINPUT_GAMEPAD_START,
+    XINPUT_GAMEPAD_LEFT_THUMB, XINPUT_GAMEPAD_RIGHT_THUMB,
+    XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_DPAD_DOWN,
+    XINPUT_GAMEPAD_DPAD_LEFT, XINPUT_GAMEPAD_DPAD_RIGHT,
+};
+
+use super::{xinput_key, Button, Buttons, DeviceId, DeviceInfo, GamepadDevice, GamepadState, InputBackend, PollError, XINPUT_USERS};
+
+const BUTTON_FLAGS: [(XINPUT_GAMEPAD_BUTTON_FLAGS, Button); 14] = [
+    (XINPUT_GAMEPAD_A, Button::A),
+    (XINPUT_GAMEPAD_B, Button::B),
+    (XINPUT_GAMEPAD_X, Button::X),
+    (XINPUT_GAMEPAD_Y, Button::Y),
+    (XINPUT_GAMEPAD_LEFT_SHOULDER, Button::LeftShoulder),
+    (XINPUT_GAMEPAD_RIGHT_SHOULDER, Button::RightShoulder),
+    (XINPUT_GAMEPAD_BACK, Button::Back),
+    (XINPUT_GAMEPAD_START, Button::Start),
+    (XINPUT_GAMEPAD_LEFT_THUMB, Button::LeftStick),
+    (XINPUT_GAMEPAD_RIGHT_THUMB, Button::RightStick),
+    (XINPUT_GAMEPAD_DPAD_UP, Button::DpadUp),
+    (XINPUT_GAMEPAD_DPAD_DOWN, Button::DpadDown),
+    (XINPUT_GAMEPAD_DPAD_LEFT, Button::DpadLeft),
+    (XINPUT_GAMEPAD_DPAD_RIGHT, Button::DpadRight),
+];
+
+fn read_xinput(user_index: u32) -> Option<XINPUT_GAMEPAD> {
+    unsafe {
+        let mut state = XINPUT_STATE::default();
+        let res = XInputGetState(user_index, &mut state);
+        if res == 0 {
+            Some(state.Gamepad)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct XInputBackend;
+
+impl InputBackend for XInputBackend {
+    fn enumerate(&mut self) -> Vec<DeviceInfo> {
+        (0..XINPUT_USERS)
+            .filter(|&i| read_xinput(i).is_some())
+            .map(|i| DeviceInfo {
+                id: DeviceId::Slot(i),
+                name: format!("XInput controller {}", i),
+                key: xinput_key(i),
+            })
+            .collect()
+    }
+
+    fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+        match info.id {
+            DeviceId::Slot(index) => Some(Box::new(XInputDevice { index })),
+            _ => None,
+        }
+    }
+}
+
+struct XInputDevice {
+    index: u32,
+}
+
+impl GamepadDevice for XInputDevice {
+    fn poll(&mut self) -> Result<GamepadState, PollError> {
+        let pad = read_xinput(self.index).ok_or(PollError::Disconnected)?;
+
+        let mut buttons = Buttons::default();
+        for (flag, button) in BUTTON_FLAGS {
+            buttons.set(button, pad.wButtons.0 & flag.0 != 0);
+        }
+
+        Ok(GamepadState {
+            left_stick: (pad.sThumbLX as f32, pad.sThumbLY as f32),
+            right_stick: (pad.sThumbRX as f32, pad.sThumbRY as f32),
+            left_trigger: pad.bLeftTrigger as f32 / 255.0,
+            right_trigger: pad.bRightTrigger as f32 / 255.0,
+            buttons,
+            motion: None,
+            touchpad: None,
+        })
+    }
+}
@@ -0,0 +1,226 @@
+//! Per-device stick calibration, kept in calibration.toml next to config.toml
+//! and keyed by `DeviceInfo::key`. Applied to each controller as it's read,
+//! before the sticks are combined and the deadzone is applied.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::GamepadState;
+use crate::settings;
+
+const MAX_VAL: f32 = 32767.0;
+
+/// Directions the edge of the stick's range is sampled in while it's rotated
+const SECTORS: usize = 36;
+
+/// Raw readings of one axis at rest and at both ends, in the XInput range the
+/// backends report.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AxisCalibration {
+    pub center: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for AxisCalibration {
+    fn default() -> Self {
+        Self { center: 0.0, min: -32768.0, max: 32767.0 }
+    }
+}
+
+impl AxisCalibration {
+    /// Maps a raw reading so `center` reads 0 and `min` and `max` reach full
+    /// deflection. Each side is scaled on its own, since worn sticks are
+    /// rarely symmetric.
+    pub fn apply(&self, value: f32) -> f32 {
+        let offset = value - self.center;
+        let span = if offset >= 0.0 { self.max - self.center } else { self.center - self.min };
+        if span <= 0.0 {
+            return 0.0;
+        }
+        (offset / span * MAX_VAL).clamp(-MAX_VAL, MAX_VAL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StickCalibration {
+    #[serde(default)]
+    pub x: AxisCalibration,
+    #[serde(default)]
+    pub y: AxisCalibration,
+}
+
+impl StickCalibration {
+    pub fn apply(&self, stick: (f32, f32)) -> (f32, f32) {
+        (self.x.apply(stick.0), self.y.apply(stick.1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceCalibration {
+    #[serde(default)]
+    pub left_stick: StickCalibration,
+    #[serde(default)]
+    pub right_stick: StickCalibration,
+}
+
+impl DeviceCalibration {
+    pub fn apply(&self, state: &mut GamepadState) {
+        state.left_stick = self.left_stick.apply(state.left_stick);
+        state.right_stick = self.right_stick.apply(state.right_stick);
+    }
+}
+
+/// Every calibrated controller, by `DeviceInfo::key`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Calibrations {
+    #[serde(default)]
+    pub devices: HashMap<String, DeviceCalibration>,
+}
+
+impl Calibrations {
+    pub fn get(&self, key: &str) -> Option<&DeviceCalibration> {
+        self.devices.get(key)
+    }
+}
+
+fn calibration_path() -> Result<PathBuf> {
+    Ok(settings::config_dir()?.join("calibration.toml"))
+}
+
+/// Reads calibration.toml; no file means nothing is calibrated yet.
+pub fn load_calibrations() -> Result<Calibrations> {
+    let path = calibration_path()?;
+    if !path.exists() {
+        return Ok(Calibrations::default());
+    }
+    let txt = fs::read_to_string(&path).context("Reading calibration.toml")?;
+    let c: Calibrations = toml::from_str(&txt).context("Parsing calibration.toml")?;
+    Ok(c)
+}
+
+/// Like `load_calibrations`, but a broken calibration.toml is logged and
+/// counts as nothing calibrated, so it can't keep anything from starting.
+pub fn load_calibrations_or_default() -> Calibrations {
+    load_calibrations().unwrap_or_else(|e| {
+        println!("Failed to load calibration: {:#}", e);
+        Calibrations::default()
+    })
+}
+
+/// Writes calibration.toml.
+pub fn save_calibrations(c: &Calibrations) -> Result<()> {
+    let path = calibration_path()?;
+    let txt = toml::to_string_pretty(c).context("Serializing calibration")?;
+    fs::write(&path, txt).context("Writing calibration.toml")?;
+    Ok(())
+}
+
+/// What the calibration wizard found out about a stick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickMeasurement {
+    pub calibration: StickCalibration,
+    /// Resting position, as a fraction of full deflection
+    pub drift: (f32, f32),
+    /// Largest wobble around the resting position, as a fraction of full deflection
+    pub noise: f32,
+    /// Mean distance of the stick's outer edge from a perfect circle after
+    /// calibration, as a fraction of full deflection
+    pub circularity_error: f32,
+}
+
+/// Collects raw stick readings for the calibration wizard: first with the
+/// stick at rest, then while it's rotated around its edge.
+#[derive(Debug, Clone, Default)]
+pub struct StickRecorder {
+    rest: Vec<(f32, f32)>,
+    edge: Vec<(f32, f32)>,
+}
+
+impl StickRecorder {
+    pub fn record_rest(&mut self, stick: (f32, f32)) {
+        self.rest.push(stick);
+    }
+
+    pub fn record_edge(&mut self, stick: (f32, f32)) {
+        self.edge.push(stick);
+    }
+
+    fn center(&self) -> (f32, f32) {
+        if self.rest.is_empty() {
+            return (0.0, 0.0);
+        }
+        let (x, y) = self.rest.iter().fold((0.0, 0.0), |sum, s| (sum.0 + s.0, sum.1 + s.1));
+        (x / self.rest.len() as f32, y / self.rest.len() as f32)
+    }
+
+    /// Which of `SECTORS` directions `stick` points in, or `None` near the center.
+    fn sector(stick: (f32, f32), center: (f32, f32)) -> Option<usize> {
+        let (x, y) = (stick.0 - center.0, stick.1 - center.1);
+        if (x * x + y * y).sqrt() < MAX_VAL * 0.5 {
+            return None;
+        }
+        let turn = (y.atan2(x) / std::f32::consts::TAU).rem_euclid(1.0);
+        Some(((turn * SECTORS as f32) as usize).min(SECTORS - 1))
+    }
+
+    /// Fraction of directions the stick has been pushed out to so far.
+    pub fn coverage(&self) -> f32 {
+        let center = self.center();
+        let mut seen = [false; SECTORS];
+        for &stick in &self.edge {
+            if let Some(sector) = Self::sector(stick, center) {
+                seen[sector] = true;
+            }
+        }
+        seen.iter().filter(|&&s| s).count() as f32 / SECTORS as f32
+    }
+
+    /// Turns the readings into a calibration, or `None` until both steps have samples.
+    pub fn finish(&self) -> Option<StickMeasurement> {
+        if self.rest.is_empty() || self.edge.is_empty() {
+            return None;
+        }
+        let center = self.center();
+        let noise = self
+            .rest
+            .iter()
+            .map(|s| ((s.0 - center.0).powi(2) + (s.1 - center.1).powi(2)).sqrt())
+            .fold(0.0, f32::max)
+            / MAX_VAL;
+
+        let (mut min, mut max) = (center, center);
+        for &(x, y) in &self.edge {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        // Readings are whole numbers, keep the file tidy
+        let calibration = StickCalibration {
+            x: AxisCalibration { center: center.0.round(), min: min.0, max: max.0 },
+            y: AxisCalibration { center: center.1.round(), min: min.1, max: max.1 },
+        };
+
+        // Outer edge per direction once calibrated; a perfect circle is 1 everywhere
+        let mut edge = [None::<f32>; SECTORS];
+        for &stick in &self.edge {
+            if let Some(sector) = Self::sector(stick, center) {
+                let (x, y) = calibration.apply(stick);
+                let radius = (x * x + y * y).sqrt() / MAX_VAL;
+                edge[sector] = Some(edge[sector].map_or(radius, |r: f32| r.max(radius)));
+            }
+        }
+        let radii: Vec<f32> = edge.iter().flatten().copied().collect();
+        let circularity_error = radii.iter().map(|r| (r - 1.0).abs()).sum::<f32>() / radii.len().max(1) as f32;
+
+        Some(StickMeasurement {
+            calibration,
+            drift: (center.0 / MAX_VAL, center.1 / MAX_VAL),
+            noise,
+            circularity_error,
+        })
+    }
+}
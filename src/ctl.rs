@@ -0,0 +1,150 @@
+//! `controller_app ctl ...`: controls the running tray from scripts over the
+//! IPC channel. Changes apply right away but aren't saved to config.toml.
+
+use anyhow::{bail, Context, Result};
+
+use crate::inputs::ListedDevice;
+use crate::ipc::Client;
+use crate::settings::InputType;
+
+const USAGE: &str = "Usage: controller_app ctl <command>
+
+Commands:
+  enable                  Start moving the cursor
+  disable                 Stop moving the cursor
+  toggle                  Switch between enabled and disabled
+  profile <name>          Apply profiles/<name>.toml from the config folder
+  set <setting> <value>   Change one setting, named as in config.toml
+  status [--json]         Show whether it's enabled, the input and the controllers";
+
+/// What `status` reports.
+struct Status {
+    enabled: bool,
+    input_type: Option<InputType>,
+    sensitivity: f32,
+    devices: Vec<ListedDevice>,
+}
+
+impl Status {
+    fn query(tray: &mut Client) -> Result<Self> {
+        let mut status = Status { enabled: false, input_type: None, sensitivity: 0.0, devices: Vec::new() };
+        for line in tray.request("status", None)? {
+            let Some((name, value)) = line.split_once('\t') else {
+                continue;
+            };
+            match name {
+                "enabled" => status.enabled = value == "true",
+                "input_type" => status.input_type = toml::Value::String(value.to_string()).try_into().ok(),
+                "sensitivity" => status.sensitivity = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+        }
+        status.devices = tray.devices()?;
+        Ok(status)
+    }
+
+    fn print(&self) {
+        println!("{}", if self.enabled { "Enabled" } else { "Disabled" });
+        println!("Input: {}", self.input_type.map_or("Unknown", |t| t.label()));
+        println!("Sensitivity: {:.2}", self.sensitivity);
+        if self.devices.is_empty() {
+            println!("No controllers found");
+        }
+        for device in &self.devices {
+            let role = device.role.map_or("Not in use".to_string(), |role| format!("Stick {}", role + 1));
+            println!("{}: {} ({})", role, device.info.name, device.info.key);
+        }
+    }
+
+    fn print_json(&self) {
+        let devices: Vec<String> = self
+            .devices
+            .iter()
+            .map(|device| {
+                format!(
+                    "{{\"key\":{},\"name\":{},\"stick\":{}}}",
+                    json_string(&device.info.key),
+                    json_string(&device.info.name),
+                    device.role.map_or("null".to_string(), |role| (role + 1).to_string())
+                )
+            })
+            .collect();
+        println!(
+            "{{\"enabled\":{},\"input_type\":{},\"sensitivity\":{},\"devices\":[{}]}}",
+            self.enabled,
+            self.input_type.map_or("null".to_string(), |t| json_string(&format!("{:?}", t))),
+            self.sensitivity,
+            devices.join(",")
+        );
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The app is built as a GUI program on Windows, so it has to borrow the
+/// console of whatever started it to print anything.
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+pub fn run(args: &[String]) -> Result<()> {
+    #[cfg(windows)]
+    attach_console();
+
+    // Each request is one line, a line break would start another
+    if args.iter().any(|arg| arg.contains(['\n', '\r'])) {
+        bail!("Arguments can't contain line breaks");
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let request = match args.as_slice() {
+        [switch @ ("enable" | "disable" | "toggle")] => switch.to_string(),
+        ["profile", name] => format!("profile {}", name),
+        ["set", name, value] => format!("set {} {}", name, value),
+        ["status"] | ["status", "--json"] => "status".to_string(),
+        _ => bail!("{}", USAGE),
+    };
+
+    let mut tray = Client::connect().context("Controller Tray isn't running, start it before using ctl")?;
+    if request == "status" {
+        let status = Status::query(&mut tray)?;
+        if args.contains(&"--json") {
+            status.print_json();
+        } else {
+            status.print();
+        }
+        return Ok(());
+    }
+
+    for line in tray.request(&request, None)? {
+        if let Some(enabled) = line.strip_prefix("enabled\t") {
+            println!("{}", if enabled == "true" { "Enabled" } else { "Disabled" });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_breaks_in_arguments_are_refused_before_connecting() {
+        let args = ["profile".to_string(), "work\nsettings 999999".to_string()];
+        let error = run(&args).unwrap_err();
+        assert!(error.to_string().contains("line breaks"), "{}", error);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::settings::{ResponseCurve, Settings};
+
+const MAX_VAL: f32 = 32767.0;
+
+/// Point on a cubic bezier from (0,0) to (1,1) with control points `p1` and `p2`.
+fn bezier_at(t: f32, p1: f32, p2: f32) -> f32 {
+    let u = 1.0 - t;
+    3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+}
+
+/// Evaluates a CSS-style cubic-bezier(x1, y1, x2, y2) at `x`.
+fn custom_bezier(x: f32, points: &[[f32; 2]; 2]) -> f32 {
+    // x1/x2 are kept in 0..=1 so x(t) is monotonic and bisection always converges
+    let (x1, x2) = (points[0][0].clamp(0.0, 1.0), points[1][0].clamp(0.0, 1.0));
+    let (mut lo, mut hi) = (0.0f32, 1.0f32);
+    for _ in 0..24 {
+        let mid = (lo + hi) / 2.0;
+        if bezier_at(mid, x1, x2) < x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    bezier_at((lo + hi) / 2.0, points[0][1], points[1][1])
+}
+
+/// Maps a normalised deflection (0..=1) to a normalised speed (0..=1).
+pub fn evaluate(curve: ResponseCurve, strength: f32, points: &[[f32; 2]; 2], x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    let y = match curve {
+        ResponseCurve::Linear => x,
+        ResponseCurve::Power => x.powf(strength.max(0.01)),
+        ResponseCurve::Exponential => {
+            if strength.abs() < 1e-3 {
+                x
+            } else {
+                (strength * x).exp_m1() / strength.exp_m1()
+            }
+        }
+        ResponseCurve::SCurve => {
+            let k = strength.max(0.01);
+            let a = x.powf(k);
+            a / (a + (1.0 - x).powf(k))
+        }
+        ResponseCurve::Custom => custom_bezier(x, points),
+    };
+    y.clamp(0.0, 1.0)
+}
+
+/// Reshapes the stick magnitude through the response curve, keeping its direction.
+pub fn apply(settings: &Settings, (x, y): (f32, f32)) -> (f32, f32) {
+    let mag = (x * x + y * y).sqrt();
+    if settings.response_curve == ResponseCurve::Linear || mag <= 0.0 {
+        return (x, y);
+    }
+
+    // Square deadzones let diagonals go past full deflection; keep that extra
+    // length linear so diagonals aren't slower than they were before shaping
+    let norm = mag / MAX_VAL;
+    let shaped = evaluate(settings.response_curve, settings.curve_strength, &settings.curve_points, norm)
+        * norm.max(1.0)
+        * MAX_VAL;
+    (x / mag * shaped, y / mag * shaped)
+}
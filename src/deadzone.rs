@@ -0,0 +1,134 @@
+use crate::settings::DeadzoneShape;
+
+const MAX_VAL: f32 = 32767.0;
+
+/// Maps `value` (>= 0) from `threshold..=saturation` onto `0..=MAX_VAL`.
+fn rescale(value: f32, threshold: f32, saturation: f32) -> f32 {
+    ((value - threshold) / (saturation - threshold)).clamp(0.0, 1.0) * MAX_VAL
+}
+
+/// Per-axis deadzone, rescaling the remaining range back to full deflection.
+fn apply_deadzone(value: f32, deadzone: f32, saturation: f32) -> f32 {
+    let deadzone_threshold = deadzone * MAX_VAL;
+
+    if value.abs() < deadzone_threshold {
+        0.0
+    } else {
+        // Scale the remaining range
+        value.signum() * rescale(value.abs(), deadzone_threshold, saturation)
+    }
+}
+
+fn magnitude((x, y): (f32, f32)) -> f32 {
+    (x * x + y * y).sqrt()
+}
+
+fn axial((x, y): (f32, f32), deadzone: f32, saturation: f32) -> (f32, f32) {
+    (apply_deadzone(x, deadzone, saturation), apply_deadzone(y, deadzone, saturation))
+}
+
+/// Zero inside the circle, only stretched by the outer deadzone outside it.
+fn radial(stick: (f32, f32), deadzone: f32, saturation: f32) -> (f32, f32) {
+    let mag = magnitude(stick);
+    // Also covers a centered stick with no deadzone, which would divide by zero
+    if mag == 0.0 || mag <= deadzone * MAX_VAL {
+        return (0.0, 0.0);
+    }
+
+    let scaled = (mag / saturation).min(1.0) * MAX_VAL;
+    (stick.0 / mag * scaled, stick.1 / mag * scaled)
+}
+
+/// Zero inside the circle, with the magnitude outside it rescaled so the
+/// edge of the deadzone maps to 0 and direction is preserved.
+fn scaled_radial(stick: (f32, f32), deadzone: f32, saturation: f32) -> (f32, f32) {
+    let mag = magnitude(stick);
+    let threshold = deadzone * MAX_VAL;
+    if mag == 0.0 || mag <= threshold {
+        return (0.0, 0.0);
+    }
+
+    let scaled = rescale(mag, threshold, saturation);
+    (stick.0 / mag * scaled, stick.1 / mag * scaled)
+}
+
+/// Axial deadzone whose width on each axis grows with the other axis' deflection,
+/// so it's narrow at the center and wide near the edges.
+fn bowtie((x, y): (f32, f32), deadzone: f32, saturation: f32) -> (f32, f32) {
+    let deadzone_x = deadzone * (y.abs() / MAX_VAL).min(1.0);
+    let deadzone_y = deadzone * (x.abs() / MAX_VAL).min(1.0);
+    (apply_deadzone(x, deadzone_x, saturation), apply_deadzone(y, deadzone_y, saturation))
+}
+
+/// Scaled radial to remove the center, then bowtie to make it easy to hold a straight axis.
+fn hybrid(stick: (f32, f32), deadzone: f32, saturation: f32) -> (f32, f32) {
+    // The radial pass already saturates, so the bowtie runs over the full range
+    bowtie(scaled_radial(stick, deadzone, saturation), deadzone, MAX_VAL)
+}
+
+/// Applies the inner and outer deadzone to a stick in the XInput range (-32768..=32767).
+/// Deflection beyond `1.0 - outer_deadzone` of the range counts as full deflection.
+pub fn apply(shape: DeadzoneShape, stick: (f32, f32), deadzone: f32, outer_deadzone: f32) -> (f32, f32) {
+    // Keep the rescaled range from collapsing when the two zones overlap
+    let saturation = (MAX_VAL * (1.0 - outer_deadzone)).max(deadzone * MAX_VAL + 1.0);
+
+    match shape {
+        DeadzoneShape::Axial => axial(stick, deadzone, saturation),
+        DeadzoneShape::Radial => radial(stick, deadzone, saturation),
+        DeadzoneShape::ScaledRadial => scaled_radial(stick, deadzone, saturation),
+        DeadzoneShape::Hybrid => hybrid(stick, deadzone, saturation),
+        DeadzoneShape::Bowtie => bowtie(stick, deadzone, saturation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        let close = (actual.0 - expected.0).abs() < 1.0 && (actual.1 - expected.1).abs() < 1.0;
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn centered_stick_without_deadzone_is_zero_for_every_shape() {
+        for shape in DeadzoneShape::ALL {
+            assert_eq!(apply(shape, (0.0, 0.0), 0.0, 0.0), (0.0, 0.0), "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn axial_zeroes_each_axis_on_its_own() {
+        // x is inside the deadzone, y is rescaled from 3276.7..32767
+        assert_near(apply(DeadzoneShape::Axial, (2000.0, 20000.0), 0.1, 0.0), (0.0, 18581.4));
+    }
+
+    #[test]
+    fn radial_keeps_diagonals_outside_the_circle() {
+        assert_eq!(apply(DeadzoneShape::Radial, (2000.0, 2000.0), 0.1, 0.0), (0.0, 0.0));
+        assert_near(apply(DeadzoneShape::Radial, (3000.0, 4000.0), 0.1, 0.0), (3000.0, 4000.0));
+        // The outer deadzone stretches the rest up to full deflection
+        assert_near(apply(DeadzoneShape::Radial, (30000.0, 0.0), 0.1, 0.1), (32767.0, 0.0));
+    }
+
+    #[test]
+    fn scaled_radial_starts_from_zero_at_the_edge() {
+        // Magnitude 5000 rescaled from 3276.7..32767, in the same 3:4 direction
+        assert_near(apply(DeadzoneShape::ScaledRadial, (3000.0, 4000.0), 0.1, 0.0), (1148.9, 1531.8));
+        assert_near(apply(DeadzoneShape::ScaledRadial, (32767.0, 0.0), 0.1, 0.0), (32767.0, 0.0));
+    }
+
+    #[test]
+    fn bowtie_is_narrow_at_the_center_and_wide_along_an_axis() {
+        assert_near(apply(DeadzoneShape::Bowtie, (2000.0, 0.0), 0.1, 0.0), (2000.0, 0.0));
+        assert_near(apply(DeadzoneShape::Bowtie, (2000.0, 32767.0), 0.1, 0.0), (0.0, 32767.0));
+    }
+
+    #[test]
+    fn hybrid_removes_the_center_and_snaps_near_an_axis() {
+        assert_eq!(apply(DeadzoneShape::Hybrid, (2000.0, 2000.0), 0.1, 0.0), (0.0, 0.0));
+        let (x, y) = apply(DeadzoneShape::Hybrid, (2000.0, 30000.0), 0.1, 0.0);
+        assert_eq!(x, 0.0);
+        assert!((29600.0..29800.0).contains(&y), "y = {}", y);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::backend::Motion;
+use crate::settings::Settings;
+
+/// Time constant of the smoothing applied to slow rotation, in seconds.
+const SMOOTHING_TIME: f32 = 0.125;
+
+/// Turns controller rotation into cursor motion, like lifting and moving a mouse.
+#[derive(Debug, Default)]
+pub struct GyroMouse {
+    // Exponentially smoothed (yaw, pitch) rate for the slow tier, deg/s
+    smoothed: (f32, f32),
+}
+
+impl GyroMouse {
+    /// Cursor motion in pixels for this tick, (0, 0) when gyro is off, the
+    /// ratchet button is held or the controller reports no motion.
+    pub fn update(&mut self, settings: &Settings, motion: Option<Motion>, ratchet_held: bool, dt: f32) -> (f32, f32) {
+        let motion = match motion {
+            Some(motion) if settings.gyro && !ratchet_held => motion,
+            _ => {
+                self.reset();
+                return (0.0, 0.0);
+            }
+        };
+
+        let (yaw, pitch) = self.smooth(settings, motion, dt);
+        let (yaw, pitch) = tighten(settings.gyro_tightening, yaw, pitch);
+
+        // Turning left (positive yaw) moves the cursor left, tilting up moves it up
+        (-yaw * settings.gyro_sensitivity * dt, -pitch * settings.gyro_sensitivity * dt)
+    }
+
+    /// Soft-tiered smoothing: rotation below the threshold is averaged to hide
+    /// sensor noise, faster rotation passes through untouched so flicks don't lag.
+    fn smooth(&mut self, settings: &Settings, motion: Motion, dt: f32) -> (f32, f32) {
+        let alpha = (dt / SMOOTHING_TIME).min(1.0);
+        self.smoothed.0 += (motion.yaw - self.smoothed.0) * alpha;
+        self.smoothed.1 += (motion.pitch - self.smoothed.1) * alpha;
+
+        let threshold = settings.gyro_smoothing;
+        if threshold <= 0.0 {
+            return (motion.yaw, motion.pitch);
+        }
+        let speed = (motion.yaw * motion.yaw + motion.pitch * motion.pitch).sqrt();
+        // Fully smoothed at half the threshold, fully direct at the threshold
+        let direct = ((speed - threshold / 2.0) / (threshold / 2.0)).clamp(0.0, 1.0);
+        (
+            motion.yaw * direct + self.smoothed.0 * (1.0 - direct),
+            motion.pitch * direct + self.smoothed.1 * (1.0 - direct),
+        )
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Scales rotation slower than `threshold` down towards zero, so holding the
+/// controller still doesn't drift the cursor but small aims still register.
+fn tighten(threshold: f32, yaw: f32, pitch: f32) -> (f32, f32) {
+    let speed = (yaw * yaw + pitch * pitch).sqrt();
+    if threshold <= 0.0 || speed >= threshold {
+        return (yaw, pitch);
+    }
+    let scale = speed / threshold;
+    (yaw * scale, pitch * scale)
+}
@@ -1,569 +1,965 @@
-use windows::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEINPUT, MOUSEEVENTF_MOVE};
-use windows::Win32::UI::Input::XboxController::{
-    XInputGetState, XINPUT_STATE, XINPUT_GAMEPAD,
-};
-use windows::Win32::Devices::HumanInterfaceDevice::{
-    DirectInput8Create, IDirectInput8W, IDirectInputDevice8W,
-    DIDEVICEINSTANCEW, DIDATAFORMAT, DIOBJECTDATAFORMAT,
-    DISCL_BACKGROUND, DISCL_NONEXCLUSIVE,
-    DI8DEVCLASS_GAMECTRL, DIEDFL_ATTACHEDONLY,
-    DIDF_ABSAXIS, DIPROP_RANGE, DIPROPRANGE, DIPROPHEADER,
-    DIPH_BYOFFSET,
-};
-use windows::Win32::Foundation::{HINSTANCE, BOOL};
-use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
-use windows::Win32::System::LibraryLoader::GetModuleHandleW;
-use windows::core::{GUID, Interface};
 use std::{thread, time::{Duration, Instant}};
-use std::sync::{Arc, Mutex};
-use std::cell::RefCell;
-use std::mem;
-
-use crate::settings::{self, InputType, DualStickPriority};
-
-fn send_mouse_delta(dx: i32, dy: i32) {
-    if dx == 0 && dy == 0 { return; }
-
-    unsafe {
-        let input = INPUT {
-            r#type: INPUT_MOUSE,
-            Anonymous: INPUT_0 {
-                mi: MOUSEINPUT {
-                    dx,
-                    dy,
-                    mouseData: 0,
-                    dwFlags: MOUSEEVENTF_MOVE,
-                    time: 0,
-                    dwExtraInfo: 0,
-                }
-            }
-        };
-        SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-    }
+use std::sync::{mpsc, Arc, Mutex};
+
+use tao::event_loop::EventLoopProxy;
+
+use crate::{curves, deadzone, layers};
+use crate::calibration::Calibrations;
+use crate::gyro::GyroMouse;
+use crate::layers::LayerSwitch;
+use crate::mapping::ButtonMapper;
+use crate::scroll::Scroller;
+use crate::backend::{self, DeviceInfo, GamepadDevice, GamepadState, InputBackend, PollError};
+use crate::output::{self, OutputEvent, OutputSink};
+use crate::settings::{self, InputType, DualStickPriority, PrecisionInput, ScrollSource};
+use crate::telemetry::{PadSample, RawSample, Telemetry, TelemetryFeed};
+use crate::tray::TrayEvent;
+
+/// First wait before looking for a missing controller again, doubled after
+/// every scan that finds nothing
+const SCAN_INTERVAL_MIN: Duration = Duration::from_millis(250);
+const SCAN_INTERVAL_MAX: Duration = Duration::from_secs(5);
+
+/// A controller arriving or leaving, passed on to the tray.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceEvent {
+    Connected(String),
+    Disconnected(String),
 }
 
-fn read_xinput(user_index: u32) -> Option<XINPUT_GAMEPAD> {
-    unsafe {
-        let mut state = XINPUT_STATE::default();
-        let res = XInputGetState(user_index, &mut state);
-        if res == 0 {
-            Some(state.Gamepad)
-        } else {
-            None
-        }
-    }
+/// A controller the active backend can see, as reported to the settings window.
+#[derive(Debug, Clone)]
+pub struct ListedDevice {
+    pub info: DeviceInfo,
+    /// Role it's open as (0 for stick 1), `None` if it isn't in use
+    pub role: Option<usize>,
 }
 
-fn find_first_controller() -> Option<u32> {
-    for i in 0..4 {
-        if read_xinput(i).is_some() {
-            return Some(i);
-        }
-    }
-    None
+/// Asks the polling thread for the controllers it can see; the list is sent
+/// back on the enclosed channel.
+pub type DeviceQuery = mpsc::Sender<Vec<ListedDevice>>;
+
+/// A device opened by `InputSource`, with what it was enumerated as.
+struct OpenDevice {
+    info: DeviceInfo,
+    device: Box<dyn GamepadDevice>,
+    /// Last reading before calibration, for the calibration wizard
+    raw: Option<GamepadState>,
 }
 
-const DIRECTINPUT_VERSION: u32 = 0x0800;
-
-// Standard DirectInput axis GUIDs
-static GUID_XAXIS: GUID = GUID { data1: 0xA36D02E0, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_YAXIS: GUID = GUID { data1: 0xA36D02E1, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_ZAXIS: GUID = GUID { data1: 0xA36D02E2, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_RXAXIS: GUID = GUID { data1: 0xA36D02F4, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_RYAXIS: GUID = GUID { data1: 0xA36D02F5, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_RZAXIS: GUID = GUID { data1: 0xA36D02E3, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_SLIDER: GUID = GUID { data1: 0xA36D02E4, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_POV: GUID = GUID { data1: 0xA36D02E5, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-static GUID_BUTTON: GUID = GUID { data1: 0xA36D02F0, data2: 0xC9F3, data3: 0x11CF, data4: [0xBF, 0xC7, 0x44, 0x45, 0x53, 0x54, 0x00, 0x00] };
-
-// DIJOYSTATE structure - standard DirectInput joystick format (80 bytes)
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct DIJoyState {
-    x: i32,      // 0
-    y: i32,      // 4
-    z: i32,      // 8
-    rx: i32,     // 12
-    ry: i32,     // 16
-    rz: i32,     // 20
-    slider: [i32; 2], // 24, 28
-    pov: [u32; 4],    // 32, 36, 40, 44
-    buttons: [u8; 32], // 48-79
+/// Keeps the devices for the active backend open, reconnecting when they drop.
+/// Devices are held by role, index 0 is stick 1 and index 1 stick 2, so a
+/// reconnect never swaps them.
+struct InputSource {
+    backend: Box<dyn InputBackend>,
+    /// Key of the controller pinned to each role, see `role_pins`
+    pins: Vec<Option<String>>,
+    devices: Vec<Option<OpenDevice>>,
+    next_scan: Instant,
+    scan_interval: Duration,
+    events: Vec<DeviceEvent>,
 }
 
-impl Default for DIJoyState {
-    fn default() -> Self {
+impl InputSource {
+    fn new(backend: Box<dyn InputBackend>, pins: Vec<Option<String>>) -> Self {
         Self {
-            x: 32767, y: 32767, z: 32767,  // Center values
-            rx: 32767, ry: 32767, rz: 32767,
-            slider: [32767; 2],
-            pov: [0xFFFFFFFF; 4], // -1 = centered
-            buttons: [0; 32],
+            backend,
+            devices: pins.iter().map(|_| None).collect(),
+            pins,
+            next_scan: Instant::now(),
+            scan_interval: SCAN_INTERVAL_MIN,
+            events: Vec::new(),
         }
     }
-}
 
-// Build c_dfDIJoystick equivalent data format with proper GUIDs
-fn create_joystick_data_format() -> (DIDATAFORMAT, Vec<DIOBJECTDATAFORMAT>) {
-    // DIDFT constants
-    const DIDFT_ABSAXIS: u32 = 0x00000002;
-    const DIDFT_POV: u32 = 0x00000010;
-    const DIDFT_PSHBUTTON: u32 = 0x00000004;
-    const DIDFT_OPTIONAL: u32 = 0x80000000;
-    const DIDFT_ANYINSTANCE: u32 = 0x00FFFF00;
-
-    let mut objects = Vec::new();
-
-    // X axis at offset 0
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_XAXIS as *const GUID,
-        dwOfs: 0,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // Y axis at offset 4
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_YAXIS as *const GUID,
-        dwOfs: 4,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // Z axis at offset 8
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_ZAXIS as *const GUID,
-        dwOfs: 8,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // Rx axis at offset 12
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_RXAXIS as *const GUID,
-        dwOfs: 12,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // Ry axis at offset 16
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_RYAXIS as *const GUID,
-        dwOfs: 16,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // Rz axis at offset 20
-    objects.push(DIOBJECTDATAFORMAT {
-        pguid: &GUID_RZAXIS as *const GUID,
-        dwOfs: 20,
-        dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-        dwFlags: 0,
-    });
-    // 2 sliders at offsets 24, 28
-    for i in 0..2u32 {
-        objects.push(DIOBJECTDATAFORMAT {
-            pguid: &GUID_SLIDER as *const GUID,
-            dwOfs: 24 + i * 4,
-            dwType: DIDFT_OPTIONAL | DIDFT_ABSAXIS | DIDFT_ANYINSTANCE,
-            dwFlags: 0,
-        });
+    /// Last uncalibrated reading of every open device.
+    fn raw_samples(&self) -> Vec<RawSample> {
+        self.devices
+            .iter()
+            .flatten()
+            .filter_map(|open| {
+                let raw = open.raw?;
+                Some(RawSample {
+                    key: open.info.key.clone(),
+                    name: open.info.name.clone(),
+                    left_stick: raw.left_stick,
+                    right_stick: raw.right_stick,
+                })
+            })
+            .collect()
     }
-    // 4 POVs at offsets 32, 36, 40, 44
-    for i in 0..4u32 {
-        objects.push(DIOBJECTDATAFORMAT {
-            pguid: &GUID_POV as *const GUID,
-            dwOfs: 32 + i * 4,
-            dwType: DIDFT_OPTIONAL | DIDFT_POV | DIDFT_ANYINSTANCE,
-            dwFlags: 0,
-        });
+
+    /// Every attached controller, and which role each open one fills.
+    fn list(&mut self) -> Vec<ListedDevice> {
+        self.backend
+            .enumerate()
+            .into_iter()
+            .map(|info| {
+                let role = self.devices.iter().position(|d| d.as_ref().is_some_and(|open| open.info.key == info.key));
+                ListedDevice { info, role }
+            })
+            .collect()
     }
-    // 32 buttons at offsets 48-79
-    for i in 0..32u32 {
-        objects.push(DIOBJECTDATAFORMAT {
-            pguid: &GUID_BUTTON as *const GUID,
-            dwOfs: 48 + i,
-            dwType: DIDFT_OPTIONAL | DIDFT_PSHBUTTON | DIDFT_ANYINSTANCE,
-            dwFlags: 0,
-        });
+
+    /// Names of the devices currently open.
+    fn open_names(&self) -> impl Iterator<Item = &str> {
+        self.devices.iter().flatten().map(|open| open.info.name.as_str())
     }
 
-    let format = DIDATAFORMAT {
-        dwSize: mem::size_of::<DIDATAFORMAT>() as u32,
-        dwObjSize: mem::size_of::<DIOBJECTDATAFORMAT>() as u32,
-        dwFlags: DIDF_ABSAXIS,
-        dwDataSize: mem::size_of::<DIJoyState>() as u32,
-        dwNumObjs: objects.len() as u32,
-        rgodf: std::ptr::null_mut(),
-    };
+    /// Looks for missing controllers right away on the next `connect`.
+    fn rescan_soon(&mut self, now: Instant) {
+        self.next_scan = now;
+        self.scan_interval = SCAN_INTERVAL_MIN;
+    }
 
-    (format, objects)
-}
+    /// Opens a device for every empty role. A pinned role waits for its
+    /// controller, the others take whatever isn't pinned, in enumeration order.
+    /// Enumerating is slow on some backends, so scans back off while nothing new shows up.
+    fn connect(&mut self, now: Instant) {
+        if self.devices.iter().all(Option::is_some) || now < self.next_scan {
+            return;
+        }
 
-struct DirectInputContext {
-    dinput: IDirectInput8W,
-    #[allow(dead_code)]
-    devices: Vec<IDirectInputDevice8W>,
-}
+        let mut available = self.backend.enumerate();
+        available.retain(|info| !self.devices.iter().flatten().any(|open| open.info.key == info.key));
 
-impl DirectInputContext {
-    fn new() -> Option<Self> {
-        unsafe {
-            // Initialize COM
-            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
-
-            // Get module handle
-            let hinst: HINSTANCE = GetModuleHandleW(None).ok()?.into();
-
-            // Create DirectInput8 object
-            let mut dinput: Option<IDirectInput8W> = None;
-            let hr = DirectInput8Create(
-                hinst,
-                DIRECTINPUT_VERSION,
-                &IDirectInput8W::IID,
-                &mut dinput as *mut _ as *mut *mut std::ffi::c_void,
-                None,
-            );
-
-            if hr.is_err() {
-                println!("Failed to create DirectInput8: {:?}", hr);
-                return None;
+        let mut connected = false;
+        for (role, slot) in self.devices.iter_mut().enumerate() {
+            if slot.is_some() {
+                continue;
+            }
+            let pick = match &self.pins[role] {
+                Some(key) => available.iter().position(|info| info.key == *key),
+                None => available.iter().position(|info| !self.pins.contains(&Some(info.key.clone()))),
+            };
+            let Some(index) = pick else {
+                continue;
+            };
+
+            let info = available.remove(index);
+            if let Some(device) = self.backend.open(&info) {
+                println!("Connected: {} as stick {}", info.name, role + 1);
+                self.events.push(DeviceEvent::Connected(info.name.clone()));
+                *slot = Some(OpenDevice { info, device, raw: None });
+                connected = true;
             }
+        }
 
-            let dinput = dinput?;
+        if connected && self.devices.len() == 2 && self.devices.iter().any(Option::is_none) {
+            println!("Only 1 joystick found, using single mode");
+        }
 
-            Some(DirectInputContext {
-                dinput,
-                devices: Vec::new(),
-            })
+        if connected {
+            self.scan_interval = SCAN_INTERVAL_MIN;
+        } else {
+            self.scan_interval = (self.scan_interval * 2).min(SCAN_INTERVAL_MAX);
         }
+        self.next_scan = now + self.scan_interval;
     }
 
-    fn enumerate_devices(&mut self) -> Vec<GUID> {
-        // We need to use a thread_local for the callback trampoline
-        thread_local! {
-            static GUIDS: RefCell<Vec<GUID>> = const { RefCell::new(Vec::new()) };
+    /// Polls every open device, one entry per role, dropping the ones that
+    /// report a disconnect. Empty when no device is open at all.
+    fn read(&mut self, calibrations: &Calibrations, now: Instant) -> Vec<Option<GamepadState>> {
+        if self.devices.iter().all(Option::is_none) {
+            return Vec::new();
         }
 
-        GUIDS.with(|g| g.borrow_mut().clear());
-
-        unsafe {
-            extern "system" fn enum_callback(
-                device_instance: *mut DIDEVICEINSTANCEW,
-                _context: *mut std::ffi::c_void,
-            ) -> BOOL {
-                unsafe {
-                    if !device_instance.is_null() {
-                        let instance = &*device_instance;
-                        GUIDS.with(|g| g.borrow_mut().push(instance.guidInstance));
+        let mut lost = Vec::new();
+        let states = self
+            .devices
+            .iter_mut()
+            .map(|slot| {
+                let open = slot.as_mut()?;
+                match open.device.poll() {
+                    Ok(mut state) => {
+                        open.raw = Some(state);
+                        if let Some(calibration) = calibrations.get(&open.info.key) {
+                            calibration.apply(&mut state);
+                        }
+                        Some(state)
+                    }
+                    Err(PollError::NotReady) => None,
+                    Err(PollError::Disconnected) => {
+                        lost.extend(slot.take().map(|open| open.info.name));
+                        None
                     }
                 }
-                BOOL(1) // DIENUM_CONTINUE
+            })
+            .collect();
+
+        for name in lost {
+            println!("Disconnected: {}", name);
+            self.events.push(DeviceEvent::Disconnected(name));
+            // It may be back in a moment, e.g. a wireless pad waking up
+            self.rescan_soon(now);
+        }
+        states
+    }
+}
+
+/// Picks or mixes the left sticks of several controllers, in role order
+/// (stick 1 first). Roles without a controller count as centered.
+fn combine_sticks(priority: DualStickPriority, deadzone: f32, sticks: &[(f32, f32)]) -> (f32, f32) {
+    let deadzone_threshold = deadzone * 32767.0;
+    let active = |stick: &(f32, f32)| stick.0.abs() > deadzone_threshold || stick.1.abs() > deadzone_threshold;
+
+    match priority {
+        DualStickPriority::Stick1First | DualStickPriority::Stick2First => {
+            let mut order = sticks.to_vec();
+            if priority == DualStickPriority::Stick2First && order.len() > 1 {
+                order.swap(0, 1);
             }
+            // The last in line is used even when it's inside the deadzone
+            order.iter().copied().find(active).or(order.last().copied()).unwrap_or_default()
+        }
+        DualStickPriority::LargestMagnitude => {
+            let mag = |s: (f32, f32)| s.0 * s.0 + s.1 * s.1;
+            sticks.iter().copied().fold(None, |best: Option<(f32, f32)>, stick| match best {
+                Some(best) if mag(best) >= mag(stick) => Some(best),
+                _ => Some(stick),
+            }).unwrap_or_default()
+        }
+        DualStickPriority::CombineAdditive => {
+            sticks.iter().fold((0.0, 0.0), |sum, stick| (sum.0 + stick.0, sum.1 + stick.1))
+        }
+    }
+}
+
+/// Key of the controller pinned to each role, one entry per role.
+fn role_pins(settings: &settings::Settings) -> Vec<Option<String>> {
+    match settings.input_type {
+        // A user index is the player number on the controller, so it already is a stable role
+        InputType::XInput => match settings.xinput_user_index {
+            Some(index) => vec![Some(backend::xinput_key(index))],
+            None => (0..backend::XINPUT_USERS).map(|i| Some(backend::xinput_key(i))).collect(),
+        },
+        // The Legion Go lists each detachable controller on its own, read both
+        InputType::DirectInputDual | InputType::LegionGo => {
+            vec![settings.stick1_device.clone(), settings.stick2_device.clone()]
+        }
+        _ => vec![settings.stick1_device.clone()],
+    }
+}
+
+/// Carries the fractional part of cursor motion between ticks, so slow
+/// movement isn't lost to integer truncation.
+#[derive(Debug, Default)]
+struct SubPixel {
+    x: f32,
+    y: f32,
+}
+
+impl SubPixel {
+    /// Adds this tick's motion and returns the whole pixels ready to send.
+    fn take(&mut self, dx: f32, dy: f32) -> (i32, i32) {
+        self.x += dx;
+        self.y += dy;
+        let (whole_x, whole_y) = (self.x.trunc(), self.y.trunc());
+        self.x -= whole_x;
+        self.y -= whole_y;
+        (whole_x as i32, whole_y as i32)
+    }
 
-            let _ = self.dinput.EnumDevices(
-                DI8DEVCLASS_GAMECTRL,
-                Some(enum_callback),
-                std::ptr::null_mut(),
-                DIEDFL_ATTACHEDONLY,
-            );
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Ramps speed up the longer the stick is held past the acceleration threshold.
+#[derive(Debug, Default)]
+struct Acceleration {
+    held: f32,
+}
+
+impl Acceleration {
+    /// `deflection` is the stick magnitude after the deadzone, 0..=1.
+    fn multiplier(&mut self, settings: &settings::Settings, deflection: f32, dt: f32) -> f32 {
+        if !settings.acceleration || deflection < settings.acceleration_threshold {
+            self.reset();
+            return 1.0;
         }
 
-        GUIDS.with(|g| g.borrow().clone())
+        self.held += dt;
+        let ramp = if settings.acceleration_ramp_time > 0.0 {
+            (self.held / settings.acceleration_ramp_time).min(1.0)
+        } else {
+            1.0
+        };
+        1.0 + (settings.acceleration_max - 1.0) * ramp
     }
 
-    fn create_device(&mut self, guid: &GUID) -> Option<IDirectInputDevice8W> {
-        unsafe {
-            let mut device: Option<IDirectInputDevice8W> = None;
+    fn reset(&mut self) {
+        self.held = 0.0;
+    }
+}
 
-            self.dinput
-                .CreateDevice(
-                    guid,
-                    &mut device,
-                    None, // pUnkOuter (almost always None)
-                )
-                .ok()?;
-            
-            let device = device?;
+/// Speed multiplier from the precision modifier, 1.0 when it isn't held.
+fn precision_factor(settings: &settings::Settings, pad: &GamepadState) -> f32 {
+    let trigger = match settings.precision {
+        PrecisionInput::Off => return 1.0,
+        PrecisionInput::Button => {
+            return if pad.buttons.contains(settings.precision_button) { settings.precision_scale } else { 1.0 };
+        }
+        PrecisionInput::LeftTrigger => pad.left_trigger,
+        PrecisionInput::RightTrigger => pad.right_trigger,
+    };
+    let amount = if settings.precision_analog {
+        trigger
+    } else if trigger > 0.5 {
+        1.0
+    } else {
+        0.0
+    };
+    1.0 - (1.0 - settings.precision_scale) * amount
+}
 
-            // Set cooperative level (background + nonexclusive)
-            device.SetCooperativeLevel(
-                None, // HWND - None for background
-                DISCL_BACKGROUND | DISCL_NONEXCLUSIVE,
-            ).ok()?;
+/// One iteration of the stick-to-cursor loop, independent of where the
+/// input comes from and where the output goes.
+pub struct Poller<F> {
+    open_backend: F,
+    source: Option<InputSource>,
+    current_input_type: Option<InputType>,
+    // Server the DSU backend was opened for, only compared while DSU is selected
+    dsu_server: (String, u16),
+    // Controllers pinned to each role when the devices were opened
+    pins: Vec<Option<String>>,
+    last: Instant,
+    subpixel: SubPixel,
+    acceleration: Acceleration,
+    buttons: ButtonMapper,
+    scroll: Scroller,
+    layers: LayerSwitch,
+    active_layer: Option<String>,
+    gyro: GyroMouse,
+    device_events: Vec<DeviceEvent>,
+    // What the last tick did, for the settings window's input tester
+    pad: Option<PadSample>,
+}
 
-            // Set data format using c_dfDIJoystick equivalent
-            let (mut format, mut objects) = create_joystick_data_format();
-            format.rgodf = objects.as_mut_ptr();
+impl<F> Poller<F>
+where
+    F: FnMut(&settings::Settings) -> Option<Box<dyn InputBackend>>,
+{
+    pub fn new(open_backend: F) -> Self {
+        Self {
+            open_backend,
+            source: None,
+            current_input_type: None,
+            dsu_server: (String::new(), 0),
+            pins: Vec::new(),
+            last: Instant::now(),
+            subpixel: SubPixel::default(),
+            acceleration: Acceleration::default(),
+            buttons: ButtonMapper::default(),
+            scroll: Scroller::default(),
+            layers: LayerSwitch::default(),
+            active_layer: None,
+            gyro: GyroMouse::default(),
+            device_events: Vec::new(),
+            pad: None,
+        }
+    }
 
-            let fmt_result = device.SetDataFormat(&mut format as *mut DIDATAFORMAT);
-            if let Err(e) = fmt_result {
-                println!("SetDataFormat failed: {:?}", e);
-                return None;
+    /// Latest readings for the settings window: uncalibrated sticks per
+    /// controller for the calibration wizard, and the last tick's pad for the
+    /// input tester.
+    pub fn telemetry(&self) -> Vec<Telemetry> {
+        let mut messages: Vec<Telemetry> = self
+            .source
+            .as_ref()
+            .map_or_else(Vec::new, InputSource::raw_samples)
+            .into_iter()
+            .map(Telemetry::Raw)
+            .collect();
+        messages.extend(self.pad.map(Telemetry::Pad));
+        messages
+    }
+
+    /// Controllers the input type in `settings` can see. Opens a backend
+    /// just for the listing when the open one is for another input type or
+    /// there's none, e.g. while disabled.
+    pub fn list_devices(&mut self, settings: &settings::Settings) -> Vec<ListedDevice> {
+        if let Some(source) = self.source.as_mut().filter(|_| self.current_input_type == Some(settings.input_type)) {
+            return source.list();
+        }
+        let Some(mut backend) = (self.open_backend)(settings) else {
+            return Vec::new();
+        };
+        backend.enumerate().into_iter().map(|info| ListedDevice { info, role: None }).collect()
+    }
+
+    /// Controllers connected or disconnected since the last call.
+    pub fn take_device_events(&mut self) -> Vec<DeviceEvent> {
+        std::mem::take(&mut self.device_events)
+    }
+
+    /// Name of the layer switched in by a shift button, if any.
+    pub fn active_layer(&self) -> Option<&str> {
+        self.active_layer.as_deref()
+    }
+
+    /// Drops motion carried between ticks and releases anything still held.
+    fn reset(&mut self, sink: &mut dyn OutputSink) {
+        self.subpixel.reset();
+        self.acceleration.reset();
+        self.buttons.release_all(sink);
+        self.scroll.reset();
+        self.gyro.reset();
+    }
+
+    /// Reads the controller and sends the resulting output to `sink`.
+    /// Returns how long to wait before the next tick.
+    pub fn tick(
+        &mut self,
+        settings: &settings::Settings,
+        calibrations: &Calibrations,
+        now: Instant,
+        sink: &mut dyn OutputSink,
+    ) -> Duration {
+        self.pad = None;
+        if !settings.enabled {
+            self.last = now;
+            self.reset(sink);
+            return Duration::from_millis(100);
+        }
+
+        // Check if input type changed - reinitialize if needed
+        let dsu_changed = settings.input_type == InputType::Dsu
+            && (self.dsu_server.0 != settings.dsu_host || self.dsu_server.1 != settings.dsu_port);
+        let pins = role_pins(settings);
+        if self.current_input_type != Some(settings.input_type) || dsu_changed || self.pins != pins {
+            self.current_input_type = Some(settings.input_type);
+            self.dsu_server = (settings.dsu_host.clone(), settings.dsu_port);
+            self.pins = pins.clone();
+            if let Some(source) = self.source.take() {
+                self.device_events.extend(source.open_names().map(|name| DeviceEvent::Disconnected(name.to_string())));
+            }
+            self.reset(sink);
+            println!("Switching to input type: {:?}", settings.input_type);
+            if let Some(reason) = settings.input_type.unavailable() {
+                println!("{} {}, no controllers will be read", settings.input_type.label(), reason);
             }
-            println!("SetDataFormat succeeded");
-
-            // Set axis range to 0-65535 (standard DirectInput range)
-            // We'll convert to signed in read_device
-            for offset in [0u32, 4u32] {
-                let mut prop_range = DIPROPRANGE {
-                    diph: DIPROPHEADER {
-                        dwSize: mem::size_of::<DIPROPRANGE>() as u32,
-                        dwHeaderSize: mem::size_of::<DIPROPHEADER>() as u32,
-                        dwObj: offset,
-                        dwHow: DIPH_BYOFFSET,
-                    },
-                    lMin: 0,
-                    lMax: 65535,
+        }
+
+        let source = match self.source.as_mut() {
+            Some(source) => source,
+            None => {
+                let Some(backend) = (self.open_backend)(settings) else {
+                    self.last = now;
+                    return Duration::from_millis(1000);
                 };
-                let _ = device.SetProperty(
-                    &DIPROP_RANGE,
-                    &mut prop_range.diph as *mut DIPROPHEADER,
-                );
+                self.source.insert(InputSource::new(backend, pins))
             }
+        };
 
-            // Acquire the device
-            device.Acquire().ok()?;
+        source.connect(now);
+        let states = source.read(calibrations, now);
+        self.device_events.append(&mut source.events);
 
-            Some(device)
+        if states.is_empty() {
+            self.buttons.release_all(sink);
         }
-    }
+        // Buttons and right sticks from every controller count when several are open
+        let mut merged = states.iter().flatten().fold(GamepadState::default(), |acc, s| GamepadState {
+            right_stick: (acc.right_stick.0 + s.right_stick.0, acc.right_stick.1 + s.right_stick.1),
+            left_trigger: acc.left_trigger.max(s.left_trigger),
+            right_trigger: acc.right_trigger.max(s.right_trigger),
+            buttons: acc.buttons | s.buttons,
+            motion: acc.motion.or(s.motion),
+            touchpad: acc.touchpad.or(s.touchpad),
+            ..acc
+        });
+        // Read before the modifier buttons are masked out below
+        let pressed = merged.buttons;
+        let precision = precision_factor(settings, &merged);
+        let ratchet_held = settings.gyro_ratchet.is_some_and(|b| merged.buttons.contains(b));
+
+        // Layers aren't reset with the rest, they keep the last buttons seen so a
+        // shift button held through a reconnect doesn't count as a new press and toggle again
+        let layer = self.layers.update(&settings.layers, merged.buttons).map(|i| &settings.layers[i]);
+        let layer_name = layer.map(|l| l.name.as_str());
+        if self.active_layer.as_deref() != layer_name {
+            self.active_layer = layer_name.map(str::to_string);
+        }
+        merged.buttons = layers::mask_shift_buttons(&settings.layers, merged.buttons);
+        // Modifier buttons only modify, they aren't passed on to mappings
+        if settings.precision == PrecisionInput::Button {
+            merged.buttons.set(settings.precision_button, false);
+        }
+        if let Some(ratchet) = settings.gyro_ratchet.filter(|_| settings.gyro) {
+            merged.buttons.set(ratchet, false);
+        }
+        let mappings = layer.map_or(&settings.button_mappings, |l| &l.button_mappings);
+        let scroll_source = layer.and_then(|l| l.scroll_source).unwrap_or(settings.scroll_source);
 
-    fn read_device(device: &IDirectInputDevice8W) -> Option<(i32, i32)> {
-        unsafe {
-            // Poll the device first
-            let _ = device.Poll();
+        self.buttons.update(settings, mappings, merged.buttons, now, sink);
 
-            // Read joystick state
-            let mut state = DIJoyState::default();
-            let result = device.GetDeviceState(
-                mem::size_of::<DIJoyState>() as u32,
-                &mut state as *mut _ as *mut std::ffi::c_void,
-            );
+        let (x, y) = match states.as_slice() {
+            // Time spent without a stick isn't motion once it's back
+            [] => {
+                self.last = now;
+                return Duration::from_millis(250);
+            }
+            [Some(state)] => state.left_stick,
+            [None] => {
+                self.last = now;
+                return Duration::from_millis(100);
+            }
+            sticks => {
+                let sticks: Vec<(f32, f32)> = sticks.iter().map(|s| s.unwrap_or_default().left_stick).collect();
+                combine_sticks(settings.dual_stick_priority, settings.deadzone, &sticks)
+            }
+        };
+        merged.left_stick = (x, y);
 
-            if result.is_ok() {
-                // Debug: print raw values
-                // println!("raw: x={} y={} z={} rx={} ry={} rz={}",
-                //     state.x, state.y, state.z, state.rx, state.ry, state.rz);
+        let dt = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
 
-                // Convert from DirectInput range (typically 0-65535 with center at 32767)
-                // to signed range (-32768 to 32767 with center at 0)
-                let x = state.x - 32767;
-                let y = state.y - 32767;
-                Some((x, y))
-            } else {
-                println!("GetDeviceState failed: {:?}", result);
-                // Try to reacquire if we lost the device
-                let _ = device.Acquire();
-                None
-            }
+        self.scroll.update(settings, scroll_source, &merged, dt, sink);
+
+        let (gx, gy) = self.gyro.update(settings, merged.motion, ratchet_held, dt);
+
+        let (x, y) = if scroll_source == ScrollSource::LeftStick {
+            // The left stick is scrolling instead of moving the cursor
+            self.acceleration.reset();
+            (0.0, 0.0)
+        } else {
+            self.stick_motion(settings, (x, y), dt)
+        };
+
+        let (mx, my) = ((x + gx) * precision, (y + gy) * precision);
+        let (dx, dy) = self.subpixel.take(mx, my);
+
+        self.pad = Some(PadSample {
+            left_stick: merged.left_stick,
+            right_stick: merged.right_stick,
+            buttons: pressed,
+            left_trigger: merged.left_trigger,
+            right_trigger: merged.right_trigger,
+            velocity: if dt > 0.0 { (mx / dt, my / dt) } else { (0.0, 0.0) },
+            touchpad: merged.touchpad,
+        });
+
+        // println!("dx:{} dy:{}",dx,dy);
+        if (dx, dy) != (0, 0) {
+            sink.send(OutputEvent::Move(dx, dy));
         }
+
+        Duration::from_millis(1)
     }
-}
 
-fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
-    let max_val = 32767.0;
-    let deadzone_threshold = deadzone * max_val;
+    /// Cursor motion in pixels from the left stick for this tick.
+    fn stick_motion(&mut self, settings: &settings::Settings, stick: (f32, f32), dt: f32) -> (f32, f32) {
+        let (x, y) = stick;
 
-    if value.abs() < deadzone_threshold {
-        0.0
-    } else {
-        // Scale the remaining range
-        let sign = value.signum();
-        let abs_val = value.abs();
-        sign * ((abs_val - deadzone_threshold) / (max_val - deadzone_threshold)) * max_val
+        // Swap axes if enabled
+        let (x, y) = if settings.swap_axes { (y, x) } else { (x, y) };
+
+        // Apply deadzone
+        let (x, y) = deadzone::apply(settings.deadzone_shape, (x, y), settings.deadzone, settings.outer_deadzone);
+
+        let deflection = (x * x + y * y).sqrt() / 32767.0;
+        let boost = self.acceleration.multiplier(settings, deflection, dt);
+
+        // Shape stick deflection into cursor speed
+        let (mut x, mut y) = curves::apply(settings, (x, y));
+
+        // Apply inversions
+        if settings.invert_x {
+            x = -x;
+        }
+        if settings.invert_y {
+            y = -y;
+        }
+
+        let speed = settings.sensitivity * boost * dt;
+        (x * speed, y * speed)
     }
 }
 
-pub fn spawn_polling_thread(settings: Arc<Mutex<settings::Settings>>) {
+/// How often raw readings are streamed to an open settings window
+const TELEMETRY_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Runs the poller on its own thread. `proxy` is told when the active layer
+/// changes or a controller comes and goes, so the tray tooltip can show it,
+/// `telemetry` gets readings while a settings window is subscribed, and
+/// `device_queries` are answered between ticks.
+pub fn spawn_polling_thread(
+    settings: Arc<Mutex<settings::Settings>>,
+    calibrations: Arc<Mutex<Calibrations>>,
+    telemetry: TelemetryFeed,
+    device_queries: mpsc::Receiver<DeviceQuery>,
+    proxy: EventLoopProxy<TrayEvent>,
+) {
     std::thread::spawn(move || {
-        let mut last = Instant::now();
-        let mut dinput_ctx: Option<DirectInputContext> = None;
-        let mut dinput_devices: Vec<IDirectInputDevice8W> = Vec::new();
-        let mut xinput_idx: Option<u32> = None;
-        let mut current_input_type: Option<InputType> = None;
+        let mut sink = match output::open_sink() {
+            Ok(sink) => sink,
+            Err(e) => {
+                println!("Failed to open mouse output: {:#}", e);
+                return;
+            }
+        };
+        let mut poller = Poller::new(backend::open_backend);
+        let mut shown_layer: Option<String> = None;
+        let mut last_telemetry = Instant::now();
 
         loop {
+            // Taken before the settings so a query sent right after a settings
+            // update is answered by a tick that already used them
+            let queries: Vec<DeviceQuery> = device_queries.try_iter().collect();
             let temp_settings = { settings.lock().unwrap().clone() };
+            let wait = poller.tick(&temp_settings, &calibrations.lock().unwrap(), Instant::now(), sink.as_mut());
 
-            if !temp_settings.enabled {
-                thread::sleep(Duration::from_millis(100));
-                last = Instant::now();
-                continue;
+            if poller.active_layer() != shown_layer.as_deref() {
+                shown_layer = poller.active_layer().map(str::to_string);
+                let _ = proxy.send_event(TrayEvent::LayerChanged(shown_layer.clone()));
             }
-
-            // Check if input type changed - reinitialize if needed
-            if current_input_type != Some(temp_settings.input_type) {
-                current_input_type = Some(temp_settings.input_type);
-                dinput_devices.clear();
-                dinput_ctx = None;
-                xinput_idx = None;
-                println!("Switching to input type: {:?}", temp_settings.input_type);
+            for event in poller.take_device_events() {
+                let _ = proxy.send_event(TrayEvent::Device(event));
+            }
+            if !queries.is_empty() {
+                let devices = poller.list_devices(&temp_settings);
+                for reply in queries {
+                    let _ = reply.send(devices.clone());
+                }
+            }
+            if telemetry.is_attached() && last_telemetry.elapsed() >= TELEMETRY_INTERVAL {
+                last_telemetry = Instant::now();
+                telemetry.send(&poller.telemetry());
             }
+            thread::sleep(wait);
+        }
+    });
+}
 
-            let (x, y) = match temp_settings.input_type {
-                InputType::XInput => {
-                    // Initialize XInput if needed
-                    if xinput_idx.is_none() {
-                        xinput_idx = find_first_controller();
-                        if xinput_idx.is_some() {
-                            println!("XInput: Connected controller at index {}", xinput_idx.unwrap());
-                        }
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Button, DeviceId};
+    use crate::output::{MemorySink, MouseButton};
 
-                    if let Some(idx) = xinput_idx {
-                        if let Some(pad) = read_xinput(idx) {
-                            (pad.sThumbLX as f32, pad.sThumbLY as f32)
-                        } else {
-                            // Lost controller, try to find again
-                            xinput_idx = None;
-                            thread::sleep(Duration::from_millis(100));
-                            continue;
-                        }
-                    } else {
-                        thread::sleep(Duration::from_millis(250));
-                        continue;
-                    }
-                }
+    const TICK: Duration = Duration::from_millis(10);
 
-                InputType::DirectInputSingle => {
-                    // Initialize DirectInput if needed
-                    if dinput_ctx.is_none() {
-                        dinput_ctx = DirectInputContext::new();
-                    }
+    /// Controller state shared between a test and the fake device reading it,
+    /// `None` while unplugged.
+    #[derive(Clone, Default)]
+    struct FakePad(Arc<Mutex<Option<GamepadState>>>);
 
-                    if let Some(ref mut ctx) = dinput_ctx {
-                        // Enumerate and create device if needed
-                        if dinput_devices.is_empty() {
-                            let guids = ctx.enumerate_devices();
-                            if let Some(guid) = guids.first() {
-                                if let Some(device) = ctx.create_device(guid) {
-                                    println!("DirectInput: Connected single joystick");
-                                    dinput_devices.push(device);
-                                }
-                            }
-                        }
+    impl FakePad {
+        fn plugged() -> Self {
+            FakePad(Arc::new(Mutex::new(Some(GamepadState::default()))))
+        }
 
-                        if let Some(device) = dinput_devices.first() {
-                            if let Some((x, y)) = DirectInputContext::read_device(device) {
-                                (x as f32, y as f32)
-                            } else {
-                                thread::sleep(Duration::from_millis(100));
-                                continue;
-                            }
-                        } else {
-                            thread::sleep(Duration::from_millis(250));
-                            continue;
-                        }
-                    } else {
-                        println!("Failed to initialize DirectInput");
-                        thread::sleep(Duration::from_millis(1000));
-                        continue;
-                    }
-                }
+        fn set(&self, state: Option<GamepadState>) {
+            *self.0.lock().unwrap() = state;
+        }
+    }
 
-                InputType::DirectInputDual => {
-                    // Initialize DirectInput if needed
-                    if dinput_ctx.is_none() {
-                        dinput_ctx = DirectInputContext::new();
-                    }
+    struct FakeBackend {
+        pads: Vec<FakePad>,
+    }
 
-                    if let Some(ref mut ctx) = dinput_ctx {
-                        // Enumerate and create devices if needed
-                        if dinput_devices.len() < 2 {
-                            dinput_devices.clear();
-                            let guids = ctx.enumerate_devices();
-
-                            for guid in guids.iter().take(2) {
-                                if let Some(device) = ctx.create_device(guid) {
-                                    dinput_devices.push(device);
-                                }
-                            }
-
-                            if dinput_devices.len() >= 2 {
-                                println!("DirectInput: Connected dual joysticks");
-                            } else if dinput_devices.len() == 1 {
-                                println!("DirectInput: Only 1 joystick found, using single mode");
-                            }
-                        }
+    impl InputBackend for FakeBackend {
+        fn enumerate(&mut self) -> Vec<DeviceInfo> {
+            (0..self.pads.len())
+                .filter(|&i| self.pads[i].0.lock().unwrap().is_some())
+                .map(|i| DeviceInfo { id: DeviceId::Slot(i as u32), name: format!("Fake {}", i), key: format!("fake:{}", i) })
+                .collect()
+        }
 
-                        match dinput_devices.len() {
-                            2 => {
-                                // Read both joysticks (both control full X/Y)
-                                let stick1 = DirectInputContext::read_device(&dinput_devices[0])
-                                    .map(|(x, y)| (x as f32, y as f32))
-                                    .unwrap_or((0.0, 0.0));
-                                let stick2 = DirectInputContext::read_device(&dinput_devices[1])
-                                    .map(|(x, y)| (x as f32, y as f32))
-                                    .unwrap_or((0.0, 0.0));
-
-                                // Apply priority logic
-                                let deadzone_threshold = temp_settings.deadzone * 32767.0;
-                                let stick1_active = stick1.0.abs() > deadzone_threshold || stick1.1.abs() > deadzone_threshold;
-                                let stick2_active = stick2.0.abs() > deadzone_threshold || stick2.1.abs() > deadzone_threshold;
-
-                                match temp_settings.dual_stick_priority {
-                                    DualStickPriority::Stick1First => {
-                                        if stick1_active { stick1 } else { stick2 }
-                                    }
-                                    DualStickPriority::Stick2First => {
-                                        if stick2_active { stick2 } else { stick1 }
-                                    }
-                                    DualStickPriority::LargestMagnitude => {
-                                        let mag1 = stick1.0 * stick1.0 + stick1.1 * stick1.1;
-                                        let mag2 = stick2.0 * stick2.0 + stick2.1 * stick2.1;
-                                        if mag1 >= mag2 { stick1 } else { stick2 }
-                                    }
-                                    DualStickPriority::CombineAdditive => {
-                                        (stick1.0 + stick2.0, stick1.1 + stick2.1)
-                                    }
-                                }
-                            }
-                            1 => {
-                                // Fallback to single joystick
-                                if let Some((x, y)) = DirectInputContext::read_device(&dinput_devices[0]) {
-                                    (x as f32, y as f32)
-                                } else {
-                                    thread::sleep(Duration::from_millis(100));
-                                    continue;
-                                }
-                            }
-                            _ => {
-                                thread::sleep(Duration::from_millis(250));
-                                continue;
-                            }
-                        }
-                    } else {
-                        println!("Failed to initialize DirectInput");
-                        thread::sleep(Duration::from_millis(1000));
-                        continue;
-                    }
-                }
+        fn open(&mut self, info: &DeviceInfo) -> Option<Box<dyn GamepadDevice>> {
+            let DeviceId::Slot(i) = info.id else {
+                return None;
             };
-            
-            // Swap axes if enabled
-            let (x, y) = if temp_settings.swap_axes { (y, x) } else { (x, y) };
+            Some(Box::new(FakeDevice(self.pads[i as usize].clone())))
+        }
+    }
 
-            // Apply deadzone
-            let mut x = apply_deadzone(x, temp_settings.deadzone);
-            let mut y = apply_deadzone(y, temp_settings.deadzone);
+    struct FakeDevice(FakePad);
 
-            // Apply inversions
-            if temp_settings.invert_x {
-                x = -x;
-            }
-            if temp_settings.invert_y {
-                y = -y;
+    impl GamepadDevice for FakeDevice {
+        fn poll(&mut self) -> Result<GamepadState, PollError> {
+            self.0 .0.lock().unwrap().ok_or(PollError::Disconnected)
+        }
+    }
+
+    type OpenBackend = Box<dyn FnMut(&settings::Settings) -> Option<Box<dyn InputBackend>>>;
+
+    /// Drives a `Poller` over fake pads with a clock that advances one `TICK` per call.
+    struct Rig {
+        poller: Poller<OpenBackend>,
+        settings: settings::Settings,
+        sink: MemorySink,
+        now: Instant,
+    }
+
+    impl Rig {
+        fn new(pads: Vec<FakePad>) -> Self {
+            let open: OpenBackend = Box::new(move |_| Some(Box::new(FakeBackend { pads: pads.clone() }) as Box<dyn InputBackend>));
+            let mut rig = Rig {
+                poller: Poller::new(open),
+                settings: settings::Settings::default(),
+                sink: MemorySink::default(),
+                now: Instant::now() + Duration::from_secs(1),
+            };
+            // Opens the devices, with the sticks still centered
+            rig.tick();
+            rig.sink.events.clear();
+            rig
+        }
+
+        fn tick(&mut self) {
+            self.poller.tick(&self.settings, &Calibrations::default(), self.now, &mut self.sink);
+            self.now += TICK;
+        }
+
+        fn ticks(&mut self, n: usize) {
+            for _ in 0..n {
+                self.tick();
             }
+        }
+    }
 
-            let now = Instant::now();
-            let dt = now.duration_since(last);
-            last = now;
+    fn stick(x: f32, y: f32) -> Option<GamepadState> {
+        Some(GamepadState { left_stick: (x, y), ..Default::default() })
+    }
 
-            let dx = (x * temp_settings.sensitivity * dt.as_secs_f32()) as i32;
-            let dy = (y * temp_settings.sensitivity * dt.as_secs_f32()) as i32;
+    fn pressing(button: Button) -> Option<GamepadState> {
+        let mut state = GamepadState::default();
+        state.buttons.set(button, true);
+        Some(state)
+    }
 
-            // println!("dx:{} dy:{}",dx,dy);
+    #[test]
+    fn full_right_moves_at_sensitivity_pixels_per_second() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        pad.set(stick(32767.0, 0.0));
+        rig.ticks(10);
+
+        // 32767 px/s at sensitivity 1 for 100ms
+        let (x, y) = rig.sink.total_motion();
+        assert!((3275..=3277).contains(&x), "x = {}", x);
+        assert_eq!(y, 0);
+    }
 
-            send_mouse_delta(dx, dy);
+    #[test]
+    fn stick_up_moves_cursor_up_with_default_invert() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        pad.set(stick(0.0, 32767.0));
+        rig.ticks(5);
 
-            thread::sleep(Duration::from_millis(1));
+        let (x, y) = rig.sink.total_motion();
+        assert_eq!(x, 0);
+        assert!(y < 0, "y = {}", y);
+    }
+
+    #[test]
+    fn disabled_sends_nothing() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.enabled = false;
+        pad.set(stick(32767.0, 32767.0));
+        rig.ticks(5);
+
+        assert!(rig.sink.events.is_empty(), "{:?}", rig.sink.events);
+    }
+
+    #[test]
+    fn buttons_go_through_the_mappings() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        pad.set(pressing(Button::A));
+        rig.tick();
+        pad.set(stick(0.0, 0.0));
+        rig.tick();
+
+        let buttons: Vec<OutputEvent> =
+            rig.sink.events.iter().copied().filter(|e| matches!(e, OutputEvent::Button(..))).collect();
+        assert_eq!(
+            buttons,
+            [OutputEvent::Button(MouseButton::Left, true), OutputEvent::Button(MouseButton::Left, false)]
+        );
+    }
+
+    #[test]
+    fn toggle_button_held_through_a_disable_does_not_toggle_again() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.layers = vec![settings::Layer {
+            name: "Edit".to_string(),
+            button: Button::Back,
+            activation: settings::LayerActivation::Toggle,
+            button_mappings: Vec::new(),
+            scroll_source: None,
+        }];
+        pad.set(pressing(Button::Back));
+        rig.tick();
+        assert_eq!(rig.poller.active_layer(), Some("Edit"));
+
+        rig.settings.enabled = false;
+        rig.tick();
+        rig.settings.enabled = true;
+        rig.ticks(2);
+        assert_eq!(rig.poller.active_layer(), Some("Edit"));
+    }
+
+    #[test]
+    fn unplugging_releases_held_buttons_and_reports_it() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        assert_eq!(rig.poller.take_device_events(), [DeviceEvent::Connected("Fake 0".to_string())]);
+
+        pad.set(pressing(Button::A));
+        rig.tick();
+        pad.set(None);
+        rig.tick();
+
+        assert_eq!(rig.sink.events.last(), Some(&OutputEvent::Button(MouseButton::Left, false)));
+        assert_eq!(rig.poller.take_device_events(), [DeviceEvent::Disconnected("Fake 0".to_string())]);
+    }
+
+    #[test]
+    fn time_spent_unplugged_does_not_jump_the_cursor_on_reconnect() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        pad.set(stick(32767.0, 0.0));
+        rig.tick();
+        let (one_tick, _) = rig.sink.total_motion();
+        assert!(one_tick > 0);
+
+        // Unplugged for 5 seconds, ticking as slowly as the poller asks to
+        pad.set(None);
+        rig.tick();
+        for _ in 0..20 {
+            rig.now += Duration::from_millis(250);
+            rig.tick();
         }
-    });
-}
\ No newline at end of file
+        rig.sink.events.clear();
+        pad.set(stick(32767.0, 0.0));
+        while moves(&rig.sink) == 0 {
+            rig.now += Duration::from_millis(250);
+            rig.tick();
+        }
+
+        // Only the last wait counts, not the 500 ticks spent unplugged
+        let (x, _) = rig.sink.total_motion();
+        assert!(x < one_tick * 50, "moved {} after reconnecting, {} per tick", x, one_tick);
+    }
+
+    #[test]
+    fn dual_mode_uses_stick_2_when_stick_1_is_centered() {
+        let (pad1, pad2) = (FakePad::plugged(), FakePad::plugged());
+        let mut rig = Rig::new(vec![pad1.clone(), pad2.clone()]);
+        rig.settings.input_type = InputType::DirectInputDual;
+        // Switching input type reopens both pads
+        rig.tick();
+        rig.tick();
+        rig.sink.events.clear();
+
+        pad2.set(stick(-32767.0, 0.0));
+        rig.ticks(5);
+
+        assert!(rig.sink.total_motion().0 < 0);
+    }
+
+    #[test]
+    fn small_constant_motion_adds_up_to_a_pixel() {
+        let mut subpixel = SubPixel::default();
+        let sent: Vec<(i32, i32)> = (0..10).map(|_| subpixel.take(0.1, -0.1)).collect();
+
+        // Nothing is lost to truncation, so ten tenths make a whole pixel
+        assert!(sent[..9].iter().all(|&d| d == (0, 0)), "{:?}", sent);
+        let total = sent.iter().fold((0, 0), |sum, d| (sum.0 + d.0, sum.1 + d.1));
+        assert_eq!(total, (1, -1));
+    }
+
+    #[test]
+    fn subpixel_reset_drops_the_leftover_fraction() {
+        let mut subpixel = SubPixel::default();
+        assert_eq!(subpixel.take(0.9, 0.9), (0, 0));
+        subpixel.reset();
+        assert_eq!(subpixel.take(0.2, 0.2), (0, 0));
+    }
+
+    #[test]
+    fn centered_radial_stick_without_deadzone_keeps_the_cursor_working() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.deadzone = 0.0;
+        rig.settings.deadzone_shape = settings::DeadzoneShape::ScaledRadial;
+        rig.ticks(5);
+        pad.set(stick(32767.0, 0.0));
+        rig.ticks(5);
+
+        assert!(rig.sink.total_motion().0 > 0);
+    }
+
+    fn moves(sink: &MemorySink) -> usize {
+        sink.events.iter().filter(|e| matches!(e, OutputEvent::Move(..))).count()
+    }
+
+    #[test]
+    fn sensitivity_scales_the_motion() {
+        let pad = FakePad::plugged();
+        let mut full = Rig::new(vec![pad.clone()]);
+        let mut half = Rig::new(vec![pad.clone()]);
+        half.settings.sensitivity = 0.5;
+        pad.set(stick(16000.0, 0.0));
+        full.ticks(10);
+        half.ticks(10);
+
+        let (full_x, half_x) = (full.sink.total_motion().0, half.sink.total_motion().0);
+        assert!(full_x > 0);
+        assert!((full_x / 2 - half_x).abs() <= 1, "{} vs {}", full_x, half_x);
+    }
+
+    #[test]
+    fn deflection_inside_the_deadzone_does_not_move() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.deadzone = 0.1;
+        pad.set(stick(3000.0, -3000.0));
+        rig.ticks(20);
+
+        assert_eq!(moves(&rig.sink), 0);
+        assert_eq!(rig.sink.total_motion(), (0, 0));
+    }
+
+    #[test]
+    fn swap_and_invert_change_the_direction() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.swap_axes = true;
+        rig.settings.invert_y = false;
+        rig.settings.invert_x = true;
+        pad.set(stick(32767.0, 0.0));
+        rig.ticks(5);
+
+        let (x, y) = rig.sink.total_motion();
+        assert_eq!(x, 0);
+        assert!(y > 0, "y = {}", y);
+    }
+
+    #[test]
+    fn left_stick_scrolling_sends_wheel_instead_of_motion() {
+        let pad = FakePad::plugged();
+        let mut rig = Rig::new(vec![pad.clone()]);
+        rig.settings.scroll_source = ScrollSource::LeftStick;
+        pad.set(stick(0.0, 32767.0));
+        rig.ticks(50);
+
+        assert_eq!(rig.sink.total_motion(), (0, 0));
+        let wheel: i32 = rig
+            .sink
+            .events
+            .iter()
+            .map(|e| match e {
+                OutputEvent::Wheel(delta) => *delta,
+                _ => 0,
+            })
+            .sum();
+        assert!(wheel > 0, "wheel = {}", wheel);
+    }
+}
@@ -0,0 +1,320 @@
+//! Local channel between the tray and settings windows: a named pipe on
+//! Windows, a Unix socket elsewhere. Requests are one line each, answered by
+//! zero or more data lines and then `ok` or `error <message>`:
+//!
+//! - `settings <len>` followed by `len` bytes of config.toml: applies the
+//!   settings right away, without saving them
+//! - `calibration`: reloads calibration.toml
+//! - `devices`: one `device\t<key>\t<name>\t<stick>` line per controller the
+//!   current input type can see, stick being 1 or 2 for the ones in use and
+//!   0 otherwise
+//! - `subscribe`: after the `ok`, the connection carries telemetry lines
+//!   (see `telemetry`) until it's closed
+//! - `enable`, `disable`, `toggle`: turns the mapper on or off, answered by
+//!   `enabled\t<true or false>`
+//! - `profile <name>`: applies `profiles/<name>.toml` from the config folder
+//! - `set <setting> <value>`: changes one setting, named and written as in
+//!   config.toml
+//!
+//! Everything after the first space is the request's argument, so profile
+//! names and values can contain spaces.
+//! - `status`: `enabled`, `input_type` and `sensitivity` lines, each a name
+//!   and a value separated by a tab
+//!
+//! Like `settings`, the last four apply right away without saving.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::backend::{DeviceId, DeviceInfo};
+use crate::calibration::{self, Calibrations};
+use crate::inputs::{DeviceQuery, ListedDevice};
+use crate::settings::{self, Settings};
+use crate::telemetry::TelemetryFeed;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+use unix as platform;
+#[cfg(windows)]
+mod pipe;
+#[cfg(windows)]
+use pipe as platform;
+
+/// How long a `devices` request waits for the polling thread, which only
+/// answers between ticks.
+const DEVICES_TIMEOUT: Duration = Duration::from_secs(2);
+/// Largest `settings` body accepted; config.toml is a few KB.
+const MAX_SETTINGS_LEN: usize = 256 * 1024;
+
+/// What the tray shares with its IPC connections.
+#[derive(Clone)]
+pub struct TrayState {
+    pub settings: Arc<Mutex<Settings>>,
+    pub calibrations: Arc<Mutex<Calibrations>>,
+    pub telemetry: TelemetryFeed,
+    pub device_queries: mpsc::Sender<DeviceQuery>,
+}
+
+/// Listens for settings windows on a background thread, one more thread per
+/// connection. Fails if another tray is already listening.
+pub fn spawn_server(state: TrayState) -> io::Result<()> {
+    let mut listener = platform::Listener::bind()?;
+    std::thread::spawn(move || loop {
+        match listener.accept() {
+            Ok(stream) => {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = serve(stream, &state) {
+                        println!("IPC connection failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                println!("IPC accept failed: {}", e);
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: platform::Stream, state: &TrayState) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let request = line.trim_end_matches(['\r', '\n']);
+        let (verb, argument) = match request.split_once(' ') {
+            Some((verb, argument)) => (verb, Some(argument)),
+            None => (request, None),
+        };
+        let reply = match (verb, argument) {
+            ("settings", Some(len)) => {
+                let len: usize = len.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad settings length"))?;
+                if len > MAX_SETTINGS_LEN {
+                    // The body can't be skipped without reading it, so this ends the connection
+                    writeln!(writer, "error settings longer than {} bytes", MAX_SETTINGS_LEN)?;
+                    return Ok(());
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body)?;
+                match toml::from_str::<Settings>(&String::from_utf8_lossy(&body)) {
+                    Ok(settings) => {
+                        *state.settings.lock().unwrap() = settings;
+                        "ok".to_string()
+                    }
+                    Err(e) => format!("error {}", e).replace('\n', " "),
+                }
+            }
+            ("calibration", None) => match calibration::load_calibrations() {
+                Ok(calibrations) => {
+                    *state.calibrations.lock().unwrap() = calibrations;
+                    "ok".to_string()
+                }
+                Err(e) => format!("error {:#}", e),
+            },
+            ("devices", None) => {
+                let (reply, devices) = mpsc::channel();
+                let _ = state.device_queries.send(reply);
+                match devices.recv_timeout(DEVICES_TIMEOUT) {
+                    Ok(devices) => {
+                        for device in devices {
+                            let stick = device.role.map_or(0, |role| role + 1);
+                            writeln!(writer, "device\t{}\t{}\t{}", device.info.key, device.info.name, stick)?;
+                        }
+                        "ok".to_string()
+                    }
+                    Err(_) => "error the polling thread didn't answer".to_string(),
+                }
+            }
+            ("subscribe", None) => {
+                writer.write_all(b"ok\n")?;
+                writer.flush()?;
+                state.telemetry.subscribe(Box::new(writer));
+                return Ok(());
+            }
+            (switch @ ("enable" | "disable" | "toggle"), None) => {
+                let mut settings = state.settings.lock().unwrap();
+                settings.enabled = match switch {
+                    "enable" => true,
+                    "disable" => false,
+                    _ => !settings.enabled,
+                };
+                writeln!(writer, "enabled\t{}", settings.enabled)?;
+                "ok".to_string()
+            }
+            ("profile", Some(name)) => match settings::load_profile(name) {
+                Ok(profile) => {
+                    *state.settings.lock().unwrap() = profile;
+                    "ok".to_string()
+                }
+                Err(e) => format!("error {:#}", e).replace('\n', " "),
+            },
+            ("set", Some(argument)) => match argument.split_once(' ') {
+                Some((name, value)) => {
+                    let mut settings = state.settings.lock().unwrap();
+                    match settings::set_field(&settings, name, value) {
+                        Ok(updated) => {
+                            *settings = updated;
+                            "ok".to_string()
+                        }
+                        Err(e) => format!("error {:#}", e).replace('\n', " "),
+                    }
+                }
+                None => "error set needs a setting and a value".to_string(),
+            },
+            ("status", None) => {
+                let settings = state.settings.lock().unwrap().clone();
+                writeln!(writer, "enabled\t{}", settings.enabled)?;
+                writeln!(writer, "input_type\t{:?}", settings.input_type)?;
+                writeln!(writer, "sensitivity\t{}", settings.sensitivity)?;
+                "ok".to_string()
+            }
+            _ => format!("error unknown request: {}", request),
+        };
+        writer.write_all(reply.as_bytes())?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+}
+
+/// A settings window's connection to the tray.
+pub struct Client {
+    reader: BufReader<platform::Stream>,
+    writer: platform::Stream,
+}
+
+impl Client {
+    /// Fails when no tray is running.
+    pub fn connect() -> io::Result<Self> {
+        let stream = platform::connect()?;
+        Ok(Client { writer: stream.try_clone()?, reader: BufReader::new(stream) })
+    }
+
+    /// Sends one request, with `body` after the request line if given, and
+    /// returns the data lines of the reply.
+    pub fn request(&mut self, request: &str, body: Option<&str>) -> Result<Vec<String>> {
+        self.writer.write_all(request.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        if let Some(body) = body {
+            self.writer.write_all(body.as_bytes())?;
+        }
+        self.writer.flush()?;
+
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                bail!("The tray closed the connection");
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == "ok" {
+                return Ok(lines);
+            }
+            if let Some(message) = line.strip_prefix("error ") {
+                bail!("{}", message);
+            }
+            lines.push(line.to_string());
+        }
+    }
+
+    /// Applies `settings` in the tray without saving them.
+    pub fn push_settings(&mut self, settings: &Settings) -> Result<()> {
+        let text = toml::to_string(settings).context("Serializing settings")?;
+        self.request(&format!("settings {}", text.len()), Some(&text))?;
+        Ok(())
+    }
+
+    /// Controllers the tray can see, and the stick each open one is used as.
+    pub fn devices(&mut self) -> Result<Vec<ListedDevice>> {
+        let lines = self.request("devices", None)?;
+        lines
+            .iter()
+            .map(|line| {
+                let mut fields = line.split('\t');
+                let (Some("device"), Some(key), Some(name), Some(stick)) =
+                    (fields.next(), fields.next(), fields.next(), fields.next())
+                else {
+                    bail!("Unexpected reply: {}", line);
+                };
+                let stick: usize = stick.parse().with_context(|| format!("Unexpected reply: {}", line))?;
+                Ok(ListedDevice {
+                    // Only the tray opens devices, the window just needs the key
+                    info: DeviceInfo { id: DeviceId::Slot(0), name: name.to_string(), key: key.to_string() },
+                    role: stick.checked_sub(1),
+                })
+            })
+            .collect()
+    }
+
+    /// Turns this connection into a telemetry stream, for `telemetry::spawn_reader`.
+    pub fn subscribe(mut self) -> Result<impl Read + Send + 'static> {
+        self.request("subscribe", None)?;
+        Ok(self.reader)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    /// A client talking to `serve` over a socket pair, and the settings it changes.
+    fn connect() -> (Client, Arc<Mutex<Settings>>) {
+        let settings = Arc::new(Mutex::new(Settings::default()));
+        let state = TrayState {
+            settings: settings.clone(),
+            calibrations: Arc::default(),
+            telemetry: TelemetryFeed::default(),
+            device_queries: mpsc::channel().0,
+        };
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || serve(theirs, &state));
+        (Client { writer: ours.try_clone().unwrap(), reader: BufReader::new(ours) }, settings)
+    }
+
+    #[test]
+    fn set_values_can_contain_spaces() {
+        let (mut tray, settings) = connect();
+        tray.request("set dsu_host steam deck.local", None).unwrap();
+        assert_eq!(settings.lock().unwrap().dsu_host, "steam deck.local");
+    }
+
+    #[test]
+    fn set_without_a_value_is_an_error() {
+        let (mut tray, _) = connect();
+        assert!(tray.request("set sensitivity", None).is_err());
+        // The connection is still usable
+        tray.request("enable", None).unwrap();
+    }
+
+    #[test]
+    fn out_of_range_values_are_refused() {
+        let (mut tray, settings) = connect();
+        let error = tray.request("set sensitivity nan", None).unwrap_err();
+        assert!(error.to_string().contains("must be between"), "{}", error);
+        assert_eq!(settings.lock().unwrap().sensitivity, Settings::default().sensitivity);
+    }
+
+    #[test]
+    fn oversized_settings_are_refused_without_reading_them() {
+        let (mut tray, _) = connect();
+        let error = tray.request(&format!("settings {}", usize::MAX), None).unwrap_err();
+        assert!(error.to_string().contains("longer than"), "{}", error);
+    }
+
+    #[test]
+    fn arguments_keep_their_spaces_in_unknown_requests() {
+        let (mut tray, _) = connect();
+        let error = tray.request("status  --json", None).unwrap_err();
+        assert_eq!(error.to_string(), "unknown request: status  --json");
+    }
+}
@@ -0,0 +1,102 @@
+//! Pipe handles are opened for synchronous I/O, which Windows serializes per
+//! handle: a read blocks writes on the same connection until it returns. The
+//! protocol never reads and writes at the same time, so that's fine.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::windows::io::FromRawHandle;
+use std::thread;
+use std::time::Duration;
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, HANDLE};
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+    PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+pub type Stream = File;
+
+const PIPE_NAME: &str = r"\\.\pipe\controller-tray";
+const BUFFER_SIZE: u32 = 4096;
+/// Attempts made while every pipe instance is taken by other clients
+const CONNECT_RETRIES: u32 = 10;
+
+fn create_instance(first: bool) -> io::Result<HANDLE> {
+    let name: Vec<u16> = PIPE_NAME.encode_utf16().chain(Some(0)).collect();
+    let mut open_mode = PIPE_ACCESS_DUPLEX;
+    if first {
+        // Fails if another tray already owns the name
+        open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+    }
+    let handle = unsafe {
+        CreateNamedPipeW(
+            PCWSTR(name.as_ptr()),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            None,
+        )
+    };
+    if handle.is_invalid() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(handle)
+}
+
+/// Keeps an instance of the pipe waiting for the next client, so clients
+/// only find it busy in the moment between two `accept` calls.
+pub struct Listener {
+    next: Option<HANDLE>,
+}
+
+// Only moved to the accept thread, never shared
+unsafe impl Send for Listener {}
+
+impl Listener {
+    pub fn bind() -> io::Result<Self> {
+        Ok(Listener { next: Some(create_instance(true)?) })
+    }
+
+    pub fn accept(&mut self) -> io::Result<Stream> {
+        let handle = match self.next.take() {
+            Some(handle) => handle,
+            None => create_instance(false)?,
+        };
+        // Owns the handle from here, closing it if the connect fails
+        let stream = unsafe { File::from_raw_handle(handle.0 as _) };
+        if let Err(e) = unsafe { ConnectNamedPipe(handle, None) } {
+            // The client got in between creating the instance and this call
+            if e.code() != ERROR_PIPE_CONNECTED.to_hresult() {
+                return Err(io::Error::other(e));
+            }
+        }
+        self.next = create_instance(false).ok();
+        Ok(stream)
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Some(handle) = self.next {
+            let _ = unsafe { CloseHandle(handle) };
+        }
+    }
+}
+
+pub fn connect() -> io::Result<Stream> {
+    let mut attempts = 0;
+    loop {
+        match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32) && attempts < CONNECT_RETRIES => {
+                attempts += 1;
+                thread::sleep(Duration::from_millis(50));
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+use crate::settings;
+
+pub type Stream = UnixStream;
+
+const SOCKET_NAME: &str = "controller-tray.sock";
+
+/// In the user's runtime directory when there is one, so it goes away on
+/// logout, otherwise next to config.toml.
+fn socket_path() -> io::Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => settings::config_dir().map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("{:#}", e)))?,
+    };
+    Ok(dir.join(SOCKET_NAME))
+}
+
+pub struct Listener(UnixListener);
+
+impl Listener {
+    pub fn bind() -> io::Result<Self> {
+        let path = socket_path()?;
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, "another tray is already running"));
+        }
+        // Left behind by a tray that didn't exit cleanly
+        let _ = std::fs::remove_file(&path);
+        UnixListener::bind(&path).map(Listener)
+    }
+
+    pub fn accept(&mut self) -> io::Result<Stream> {
+        self.0.accept().map(|(stream, _)| stream)
+    }
+}
+
+pub fn connect() -> io::Result<Stream> {
+    UnixStream::connect(socket_path()?)
+}
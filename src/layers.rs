@@ -0,0 +1,87 @@
+use crate::backend::Buttons;
+use crate::settings::{Layer, LayerActivation};
+
+/// Works out which layer is active from the shift buttons.
+#[derive(Debug, Default)]
+pub struct LayerSwitch {
+    toggled: Option<usize>,
+    previous: Buttons,
+}
+
+impl LayerSwitch {
+    /// Returns the index of the active layer, or `None` for the base mappings.
+    /// A held momentary layer wins over a toggled one.
+    pub fn update(&mut self, layers: &[Layer], buttons: Buttons) -> Option<usize> {
+        // The config can be reloaded with fewer layers while one is toggled on
+        if self.toggled.is_some_and(|i| i >= layers.len()) {
+            self.toggled = None;
+        }
+
+        for (i, layer) in layers.iter().enumerate() {
+            let pressed = buttons.contains(layer.button) && !self.previous.contains(layer.button);
+            if layer.activation == LayerActivation::Toggle && pressed {
+                self.toggled = if self.toggled == Some(i) { None } else { Some(i) };
+            }
+        }
+        self.previous = buttons;
+
+        layers
+            .iter()
+            .position(|layer| layer.activation == LayerActivation::Momentary && buttons.contains(layer.button))
+            .or(self.toggled)
+    }
+}
+
+/// Removes every layer's shift button, those only switch layers.
+pub fn mask_shift_buttons(layers: &[Layer], mut buttons: Buttons) -> Buttons {
+    for layer in layers {
+        buttons.set(layer.button, false);
+    }
+    buttons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Button;
+
+    fn layer(button: Button, activation: LayerActivation) -> Layer {
+        Layer { name: format!("{:?}", button), button, activation, button_mappings: Vec::new(), scroll_source: None }
+    }
+
+    fn pressing(buttons: &[Button]) -> Buttons {
+        let mut set = Buttons::default();
+        for &button in buttons {
+            set.set(button, true);
+        }
+        set
+    }
+
+    #[test]
+    fn toggle_flips_on_each_press_not_while_held() {
+        let layers = [layer(Button::Back, LayerActivation::Toggle)];
+        let mut switch = LayerSwitch::default();
+        assert_eq!(switch.update(&layers, pressing(&[Button::Back])), Some(0));
+        assert_eq!(switch.update(&layers, pressing(&[Button::Back])), Some(0));
+        assert_eq!(switch.update(&layers, pressing(&[])), Some(0));
+        assert_eq!(switch.update(&layers, pressing(&[Button::Back])), None);
+    }
+
+    #[test]
+    fn momentary_wins_over_toggled_while_held() {
+        let layers = [layer(Button::Back, LayerActivation::Toggle), layer(Button::Start, LayerActivation::Momentary)];
+        let mut switch = LayerSwitch::default();
+        switch.update(&layers, pressing(&[Button::Back]));
+        assert_eq!(switch.update(&layers, pressing(&[Button::Start])), Some(1));
+        assert_eq!(switch.update(&layers, pressing(&[])), Some(0));
+    }
+
+    #[test]
+    fn toggled_layer_is_dropped_when_the_config_loses_it() {
+        let layers = [layer(Button::Back, LayerActivation::Toggle)];
+        let mut switch = LayerSwitch::default();
+        switch.update(&layers, pressing(&[Button::Back]));
+        assert_eq!(switch.update(&[], pressing(&[])), None);
+        assert_eq!(switch.update(&layers, pressing(&[])), None);
+    }
+}
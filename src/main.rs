@@ -8,13 +8,28 @@ use std::{env};
 mod tray;
 mod settings;
 mod inputs;
+mod backend;
+mod output;
+mod deadzone;
+mod curves;
+mod mapping;
+mod scroll;
+mod layers;
+mod gyro;
+mod calibration;
+mod telemetry;
+mod ipc;
+mod ctl;
 
 
 
 
 fn main() -> Result<()>{
     let args: Vec<String> = env::args().collect();
-    if args.iter().any(|a| a == "--settings"){
+    if args.get(1).is_some_and(|a| a == "ctl"){
+        ctl::run(&args[2..])
+    }
+    else if args.iter().any(|a| a == "--settings"){
         settings::run_settings_window()
     }
     else{
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::backend::{Button, Buttons};
+use crate::output::{OutputEvent, OutputSink};
+use crate::settings::{ButtonAction, ButtonMapping, Settings};
+
+fn send_action(action: &ButtonAction, pressed: bool, sink: &mut dyn OutputSink) {
+    match action {
+        ButtonAction::Mouse(button) => sink.send(OutputEvent::Button(*button, pressed)),
+        ButtonAction::Keys(chord) => {
+            // Modifiers go down first and come up last
+            if pressed {
+                for key in &chord.0 {
+                    sink.send(OutputEvent::Key(*key, true));
+                }
+            } else {
+                for key in chord.0.iter().rev() {
+                    sink.send(OutputEvent::Key(*key, false));
+                }
+            }
+        }
+    }
+}
+
+/// Presses and immediately releases an action.
+fn click(action: &ButtonAction, sink: &mut dyn OutputSink) {
+    send_action(action, true, sink);
+    send_action(action, false, sink);
+}
+
+/// Where a single controller button is in its tap/hold/double-tap sequence.
+#[derive(Debug, Clone, Default)]
+enum Gesture {
+    #[default]
+    Idle,
+    /// Down, but not long enough yet to tell a tap from a hold
+    Pending { since: Instant },
+    /// Tapped once, waiting to see whether a second press follows
+    Tapped { at: Instant },
+    /// An action is down and stays down until the button comes up
+    Held(ButtonAction),
+}
+
+/// Turns controller buttons into output. A button with only a press action
+/// holds it for as long as the button is down (e.g. to drag); one with a hold
+/// or double-tap action waits to see which gesture it is before sending anything.
+#[derive(Debug, Default)]
+pub struct ButtonMapper {
+    // Actions are stored once sent, so they're released with the same action
+    // even if the mapping changed in the meantime
+    gestures: HashMap<Button, Gesture>,
+}
+
+impl ButtonMapper {
+    /// `mappings` are the active layer's, timings come from `settings`.
+    pub fn update(
+        &mut self,
+        settings: &Settings,
+        mappings: &[ButtonMapping],
+        buttons: Buttons,
+        now: Instant,
+        sink: &mut dyn OutputSink,
+    ) {
+        let hold_time = Duration::from_secs_f32(settings.hold_time.max(0.0));
+        let double_tap_time = Duration::from_secs_f32(settings.double_tap_time.max(0.0));
+
+        for button in Button::ALL {
+            let mapping = mappings.iter().find(|m| m.button == button);
+            let down = buttons.contains(button);
+            let gesture = self.gestures.remove(&button).unwrap_or_default();
+
+            let next = step(gesture, mapping, down, now, hold_time, double_tap_time, sink);
+            if !matches!(next, Gesture::Idle) {
+                self.gestures.insert(button, next);
+            }
+        }
+    }
+
+    /// Releases everything still held, e.g. when the controller goes away.
+    /// Gestures that haven't resolved yet are dropped without sending anything.
+    pub fn release_all(&mut self, sink: &mut dyn OutputSink) {
+        for (_, gesture) in self.gestures.drain() {
+            if let Gesture::Held(action) = gesture {
+                send_action(&action, false, sink);
+            }
+        }
+    }
+}
+
+fn step(
+    gesture: Gesture,
+    mapping: Option<&ButtonMapping>,
+    down: bool,
+    now: Instant,
+    hold_time: Duration,
+    double_tap_time: Duration,
+    sink: &mut dyn OutputSink,
+) -> Gesture {
+    let released = |sink: &mut dyn OutputSink| {
+        if let Some(release) = mapping.and_then(|m| m.release.as_ref()) {
+            click(release, sink);
+        }
+    };
+
+    match (gesture, mapping) {
+        (Gesture::Held(action), _) => {
+            if down {
+                return Gesture::Held(action);
+            }
+            send_action(&action, false, sink);
+            released(sink);
+            Gesture::Idle
+        }
+        // Nothing mapped, or the mapping was removed mid-gesture
+        (_, None) => Gesture::Idle,
+        (Gesture::Idle, Some(mapping)) => {
+            if !down {
+                return Gesture::Idle;
+            }
+            if mapping.hold.is_none() && mapping.double_tap.is_none() {
+                send_action(&mapping.action, true, sink);
+                return Gesture::Held(mapping.action.clone());
+            }
+            Gesture::Pending { since: now }
+        }
+        (Gesture::Pending { since }, Some(mapping)) => {
+            if down {
+                match &mapping.hold {
+                    Some(hold) if now.duration_since(since) >= hold_time => {
+                        send_action(hold, true, sink);
+                        Gesture::Held(hold.clone())
+                    }
+                    _ => Gesture::Pending { since },
+                }
+            } else if mapping.double_tap.is_some() {
+                Gesture::Tapped { at: now }
+            } else {
+                click(&mapping.action, sink);
+                released(sink);
+                Gesture::Idle
+            }
+        }
+        (Gesture::Tapped { at }, Some(mapping)) => match &mapping.double_tap {
+            Some(double_tap) if now.duration_since(at) < double_tap_time => {
+                if !down {
+                    return Gesture::Tapped { at };
+                }
+                send_action(double_tap, true, sink);
+                Gesture::Held(double_tap.clone())
+            }
+            // No second press in time, it was a single tap after all. A press
+            // that came too late starts a gesture of its own.
+            _ => {
+                click(&mapping.action, sink);
+                released(sink);
+                if down {
+                    Gesture::Pending { since: now }
+                } else {
+                    Gesture::Idle
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{MemorySink, MouseButton};
+
+    const LEFT: ButtonAction = ButtonAction::Mouse(MouseButton::Left);
+    const RIGHT: ButtonAction = ButtonAction::Mouse(MouseButton::Right);
+    const MIDDLE: ButtonAction = ButtonAction::Mouse(MouseButton::Middle);
+
+    /// Feeds `(milliseconds, A down)` samples to a mapper with the default
+    /// timings (0.4 s hold, 0.25 s double tap) and returns what it sent.
+    fn run(mapping: ButtonMapping, samples: &[(u64, bool)]) -> Vec<OutputEvent> {
+        let settings = Settings::default();
+        let mappings = [mapping];
+        let mut mapper = ButtonMapper::default();
+        let mut sink = MemorySink::default();
+        let start = Instant::now();
+        for &(ms, down) in samples {
+            let mut buttons = Buttons::default();
+            buttons.set(Button::A, down);
+            mapper.update(&settings, &mappings, buttons, start + Duration::from_millis(ms), &mut sink);
+        }
+        sink.events
+    }
+
+    fn gestures() -> ButtonMapping {
+        ButtonMapping { hold: Some(RIGHT), double_tap: Some(MIDDLE), ..ButtonMapping::new(Button::A, LEFT) }
+    }
+
+    fn down(button: MouseButton) -> OutputEvent {
+        OutputEvent::Button(button, true)
+    }
+
+    fn up(button: MouseButton) -> OutputEvent {
+        OutputEvent::Button(button, false)
+    }
+
+    #[test]
+    fn press_only_mapping_follows_the_button() {
+        let events = run(ButtonMapping::new(Button::A, LEFT), &[(0, true), (500, true), (600, false)]);
+        assert_eq!(events, [down(MouseButton::Left), up(MouseButton::Left)]);
+    }
+
+    #[test]
+    fn tap_clicks_once_the_double_tap_window_has_passed() {
+        let events = run(gestures(), &[(0, true), (100, false), (200, false)]);
+        assert!(events.is_empty(), "a second press could still follow");
+
+        let events = run(gestures(), &[(0, true), (100, false), (200, false), (400, false)]);
+        assert_eq!(events, [down(MouseButton::Left), up(MouseButton::Left)]);
+    }
+
+    #[test]
+    fn holding_past_the_threshold_holds_the_hold_action() {
+        let events = run(gestures(), &[(0, true), (300, true)]);
+        assert!(events.is_empty());
+
+        let events = run(gestures(), &[(0, true), (300, true), (400, true), (900, true), (1000, false)]);
+        assert_eq!(events, [down(MouseButton::Right), up(MouseButton::Right)]);
+    }
+
+    #[test]
+    fn second_press_inside_the_window_is_a_double_tap() {
+        let events = run(gestures(), &[(0, true), (100, false), (200, true), (700, true), (800, false)]);
+        assert_eq!(events, [down(MouseButton::Middle), up(MouseButton::Middle)]);
+    }
+
+    #[test]
+    fn second_press_outside_the_window_is_two_taps() {
+        // No sample between the release and the late press
+        let events = run(gestures(), &[(0, true), (100, false), (400, true), (500, false), (800, false)]);
+        assert_eq!(
+            events,
+            [down(MouseButton::Left), up(MouseButton::Left), down(MouseButton::Left), up(MouseButton::Left)]
+        );
+    }
+
+    #[test]
+    fn release_action_is_clicked_after_a_hold_ends() {
+        let mapping = ButtonMapping { hold: Some(RIGHT), release: Some(MIDDLE), ..ButtonMapping::new(Button::A, LEFT) };
+        let events = run(mapping.clone(), &[(0, true), (500, true)]);
+        assert_eq!(events, [down(MouseButton::Right)]);
+
+        let events = run(mapping, &[(0, true), (500, true), (600, false)]);
+        assert_eq!(
+            events,
+            [down(MouseButton::Right), up(MouseButton::Right), down(MouseButton::Middle), up(MouseButton::Middle)]
+        );
+    }
+
+    #[test]
+    fn release_all_lets_go_of_a_held_action() {
+        let settings = Settings::default();
+        let mut mapper = ButtonMapper::default();
+        let mut sink = MemorySink::default();
+        let mut buttons = Buttons::default();
+        buttons.set(Button::A, true);
+        mapper.update(&settings, &[ButtonMapping::new(Button::A, LEFT)], buttons, Instant::now(), &mut sink);
+        mapper.release_all(&mut sink);
+        assert_eq!(sink.events, [down(MouseButton::Left), up(MouseButton::Left)]);
+    }
+}
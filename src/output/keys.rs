@@ -0,0 +1,177 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// Each key with its config name, Windows virtual-key code and Linux KEY_* code
+macro_rules! keys {
+    ($($variant:ident => $name:literal, $vk:literal, $linux:literal;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Key {
+            $($variant,)*
+        }
+
+        impl Key {
+            pub const ALL: &'static [Key] = &[$(Key::$variant,)*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Key::$variant => $name,)*
+                }
+            }
+
+            /// Windows virtual-key code
+            #[cfg_attr(not(windows), allow(dead_code))]
+            pub fn vk(self) -> u16 {
+                match self {
+                    $(Key::$variant => $vk,)*
+                }
+            }
+
+            /// Linux input event code (KEY_*)
+            #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+            pub fn linux_code(self) -> u16 {
+                match self {
+                    $(Key::$variant => $linux,)*
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    Ctrl => "Ctrl", 0x11, 29;
+    Shift => "Shift", 0x10, 42;
+    Alt => "Alt", 0x12, 56;
+    Win => "Win", 0x5B, 125;
+    Enter => "Enter", 0x0D, 28;
+    Escape => "Esc", 0x1B, 1;
+    Tab => "Tab", 0x09, 15;
+    Space => "Space", 0x20, 57;
+    Backspace => "Backspace", 0x08, 14;
+    Insert => "Insert", 0x2D, 110;
+    Delete => "Delete", 0x2E, 111;
+    Home => "Home", 0x24, 102;
+    End => "End", 0x23, 107;
+    PageUp => "PageUp", 0x21, 104;
+    PageDown => "PageDown", 0x22, 109;
+    Up => "Up", 0x26, 103;
+    Down => "Down", 0x28, 108;
+    Left => "Left", 0x25, 105;
+    Right => "Right", 0x27, 106;
+    A => "A", 0x41, 30;
+    B => "B", 0x42, 48;
+    C => "C", 0x43, 46;
+    D => "D", 0x44, 32;
+    E => "E", 0x45, 18;
+    F => "F", 0x46, 33;
+    G => "G", 0x47, 34;
+    H => "H", 0x48, 35;
+    I => "I", 0x49, 23;
+    J => "J", 0x4A, 36;
+    K => "K", 0x4B, 37;
+    L => "L", 0x4C, 38;
+    M => "M", 0x4D, 50;
+    N => "N", 0x4E, 49;
+    O => "O", 0x4F, 24;
+    P => "P", 0x50, 25;
+    Q => "Q", 0x51, 16;
+    R => "R", 0x52, 19;
+    S => "S", 0x53, 31;
+    T => "T", 0x54, 20;
+    U => "U", 0x55, 22;
+    V => "V", 0x56, 47;
+    W => "W", 0x57, 17;
+    X => "X", 0x58, 45;
+    Y => "Y", 0x59, 21;
+    Z => "Z", 0x5A, 44;
+    Num0 => "0", 0x30, 11;
+    Num1 => "1", 0x31, 2;
+    Num2 => "2", 0x32, 3;
+    Num3 => "3", 0x33, 4;
+    Num4 => "4", 0x34, 5;
+    Num5 => "5", 0x35, 6;
+    Num6 => "6", 0x36, 7;
+    Num7 => "7", 0x37, 8;
+    Num8 => "8", 0x38, 9;
+    Num9 => "9", 0x39, 10;
+    F1 => "F1", 0x70, 59;
+    F2 => "F2", 0x71, 60;
+    F3 => "F3", 0x72, 61;
+    F4 => "F4", 0x73, 62;
+    F5 => "F5", 0x74, 63;
+    F6 => "F6", 0x75, 64;
+    F7 => "F7", 0x76, 65;
+    F8 => "F8", 0x77, 66;
+    F9 => "F9", 0x78, 67;
+    F10 => "F10", 0x79, 68;
+    F11 => "F11", 0x7A, 87;
+    F12 => "F12", 0x7B, 88;
+    VolumeMute => "VolumeMute", 0xAD, 113;
+    VolumeDown => "VolumeDown", 0xAE, 114;
+    VolumeUp => "VolumeUp", 0xAF, 115;
+    MediaNext => "MediaNext", 0xB0, 163;
+    MediaPrev => "MediaPrev", 0xB1, 165;
+    MediaPlayPause => "MediaPlayPause", 0xB3, 164;
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Key, String> {
+        let alias = match s.to_ascii_lowercase().as_str() {
+            "control" => "Ctrl",
+            "escape" => "Esc",
+            "return" => "Enter",
+            "del" => "Delete",
+            "super" | "meta" | "windows" => "Win",
+            "pgup" => "PageUp",
+            "pgdn" => "PageDown",
+            _ => s,
+        };
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.name().eq_ignore_ascii_case(alias))
+            .ok_or_else(|| format!("Unknown key {:?}", s))
+    }
+}
+
+/// Keys pressed together, written as e.g. "Ctrl+Shift+Esc". Pressed in order
+/// and released in reverse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord(pub Vec<Key>);
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyChord, String> {
+        let keys = s
+            .split('+')
+            .map(|part| part.trim().parse())
+            .collect::<Result<Vec<Key>, String>>()?;
+        Ok(KeyChord(keys))
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<KeyChord, String> {
+        s.parse()
+    }
+}
+
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> String {
+        chord.to_string()
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.0.iter().map(|key| key.name()).collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
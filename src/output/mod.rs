@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+mod keys;
+#[cfg(windows)]
+mod sendinput;
+#[cfg(target_os = "linux")]
+mod uinput;
+
+pub use keys::{Key, KeyChord};
+
+/// Wheel units per notch, same as Windows' WHEEL_DELTA.
+pub const WHEEL_DELTA: i32 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    pub const ALL: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MouseButton::Left => "Left Click",
+            MouseButton::Right => "Right Click",
+            MouseButton::Middle => "Middle Click",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputEvent {
+    /// Relative cursor movement in pixels
+    Move(i32, i32),
+    /// Vertical wheel in `WHEEL_DELTA` units, positive scrolls up
+    Wheel(i32),
+    /// Horizontal wheel in `WHEEL_DELTA` units, positive scrolls right
+    HWheel(i32),
+    Button(MouseButton, bool),
+    Key(Key, bool),
+}
+
+pub trait OutputSink {
+    fn send(&mut self, event: OutputEvent);
+}
+
+/// Opens the native pointer output for this platform.
+pub fn open_sink() -> anyhow::Result<Box<dyn OutputSink>> {
+    #[cfg(windows)]
+    return Ok(Box::new(sendinput::SendInputSink));
+
+    #[cfg(target_os = "linux")]
+    return Ok(Box::new(uinput::UinputSink::new()?));
+
+    #[allow(unreachable_code)]
+    Err(anyhow::anyhow!("No mouse output available on this platform"))
+}
+
+/// Records everything it's sent, so the stick-to-cursor pipeline can be checked without a real device.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pub events: Vec<OutputEvent>,
+}
+
+#[cfg(test)]
+impl MemorySink {
+    /// Sum of every `Move` recorded so far.
+    pub fn total_motion(&self) -> (i32, i32) {
+        self.events.iter().fold((0, 0), |(x, y), event| match event {
+            OutputEvent::Move(dx, dy) => (x + dx, y + dy),
+            _ => (x, y),
+        })
+    }
+}
+
+#[cfg(test)]
+impl OutputSink for MemorySink {
+    fn send(&mut self, event: OutputEvent) {
+        self.events.push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_sink_records_in_order_and_sums_moves() {
+        let mut sink = MemorySink::default();
+        sink.send(OutputEvent::Move(3, -1));
+        sink.send(OutputEvent::Button(MouseButton::Left, true));
+        sink.send(OutputEvent::Wheel(WHEEL_DELTA));
+        sink.send(OutputEvent::Move(-5, 4));
+
+        assert_eq!(sink.events.len(), 4);
+        assert_eq!(sink.events[1], OutputEvent::Button(MouseButton::Left, true));
+        assert_eq!(sink.total_motion(), (-2, 3));
+    }
+}
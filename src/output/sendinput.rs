@@ -0,0 +1,90 @@
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEINPUT, MOUSE_EVENT_FLAGS,
+    INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP, KEYEVENTF_EXTENDEDKEY, VIRTUAL_KEY,
+    MOUSEEVENTF_MOVE, MOUSEEVENTF_WHEEL, MOUSEEVENTF_HWHEEL,
+    MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
+    MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
+    MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP,
+};
+
+use super::{Key, MouseButton, OutputEvent, OutputSink};
+
+fn send_mouse_input(dx: i32, dy: i32, mouse_data: i32, flags: MOUSE_EVENT_FLAGS) {
+    unsafe {
+        let input = INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dx,
+                    dy,
+                    // Signed wheel deltas are passed through as their two's complement
+                    mouseData: mouse_data as u32,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                }
+            }
+        };
+        SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+fn send_key(key: Key, down: bool) {
+    // Navigation and media keys live on the extended part of the keyboard;
+    // without the flag Windows can read them as numpad keys
+    let extended = matches!(
+        key,
+        Key::Insert | Key::Delete | Key::Home | Key::End | Key::PageUp | Key::PageDown
+            | Key::Up | Key::Down | Key::Left | Key::Right | Key::Win
+            | Key::VolumeMute | Key::VolumeDown | Key::VolumeUp
+            | Key::MediaNext | Key::MediaPrev | Key::MediaPlayPause
+    );
+    let mut flags = KEYBD_EVENT_FLAGS(0);
+    if extended {
+        flags |= KEYEVENTF_EXTENDEDKEY;
+    }
+    if !down {
+        flags |= KEYEVENTF_KEYUP;
+    }
+
+    unsafe {
+        let input = INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(key.vk()),
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                }
+            }
+        };
+        SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+pub struct SendInputSink;
+
+impl OutputSink for SendInputSink {
+    fn send(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Move(0, 0) | OutputEvent::Wheel(0) | OutputEvent::HWheel(0) => {}
+            OutputEvent::Move(dx, dy) => send_mouse_input(dx, dy, 0, MOUSEEVENTF_MOVE),
+            OutputEvent::Wheel(delta) => send_mouse_input(0, 0, delta, MOUSEEVENTF_WHEEL),
+            OutputEvent::HWheel(delta) => send_mouse_input(0, 0, delta, MOUSEEVENTF_HWHEEL),
+            OutputEvent::Button(button, down) => {
+                let flags = match (button, down) {
+                    (MouseButton::Left, true) => MOUSEEVENTF_LEFTDOWN,
+                    (MouseButton::Left, false) => MOUSEEVENTF_LEFTUP,
+                    (MouseButton::Right, true) => MOUSEEVENTF_RIGHTDOWN,
+                    (MouseButton::Right, false) => MOUSEEVENTF_RIGHTUP,
+                    (MouseButton::Middle, true) => MOUSEEVENTF_MIDDLEDOWN,
+                    (MouseButton::Middle, false) => MOUSEEVENTF_MIDDLEUP,
+                };
+                send_mouse_input(0, 0, 0, flags);
+            }
+            OutputEvent::Key(key, down) => send_key(key, down),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Context;
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent, Key, RelativeAxisType};
+
+use super::{MouseButton, OutputEvent, OutputSink, WHEEL_DELTA};
+
+/// Virtual relative pointer and keyboard created through /dev/uinput.
+pub struct UinputSink {
+    device: VirtualDevice,
+    // Hi-res wheel units not yet sent as a full notch, vertical and horizontal
+    wheel_remainder: i32,
+    hwheel_remainder: i32,
+}
+
+impl UinputSink {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut axes = AttributeSet::<RelativeAxisType>::new();
+        axes.insert(RelativeAxisType::REL_X);
+        axes.insert(RelativeAxisType::REL_Y);
+        axes.insert(RelativeAxisType::REL_WHEEL);
+        axes.insert(RelativeAxisType::REL_WHEEL_HI_RES);
+        axes.insert(RelativeAxisType::REL_HWHEEL);
+        axes.insert(RelativeAxisType::REL_HWHEEL_HI_RES);
+
+        let mut buttons = AttributeSet::<Key>::new();
+        buttons.insert(Key::BTN_LEFT);
+        buttons.insert(Key::BTN_RIGHT);
+        buttons.insert(Key::BTN_MIDDLE);
+        for key in super::Key::ALL {
+            buttons.insert(Key::new(key.linux_code()));
+        }
+
+        let device = VirtualDeviceBuilder::new()
+            .context("Opening /dev/uinput")?
+            .name("Controller Tray Mouse")
+            .with_relative_axes(&axes)?
+            .with_keys(&buttons)?
+            .build()
+            .context("Creating uinput mouse")?;
+
+        Ok(Self { device, wheel_remainder: 0, hwheel_remainder: 0 })
+    }
+
+    /// Sends a wheel delta as HI_RES for smooth-scrolling clients and as whole
+    /// notches for everything else.
+    fn wheel(&mut self, delta: i32, horizontal: bool) {
+        let (notch_axis, hi_res_axis, remainder) = if horizontal {
+            (RelativeAxisType::REL_HWHEEL, RelativeAxisType::REL_HWHEEL_HI_RES, &mut self.hwheel_remainder)
+        } else {
+            (RelativeAxisType::REL_WHEEL, RelativeAxisType::REL_WHEEL_HI_RES, &mut self.wheel_remainder)
+        };
+
+        *remainder += delta;
+        let notches = *remainder / WHEEL_DELTA;
+        *remainder -= notches * WHEEL_DELTA;
+
+        let mut events = vec![InputEvent::new(EventType::RELATIVE, hi_res_axis.0, delta)];
+        if notches != 0 {
+            events.push(InputEvent::new(EventType::RELATIVE, notch_axis.0, notches));
+        }
+        self.emit(&events);
+    }
+
+    fn emit(&mut self, events: &[InputEvent]) {
+        if let Err(e) = self.device.emit(events) {
+            println!("uinput write failed: {}", e);
+        }
+    }
+}
+
+impl OutputSink for UinputSink {
+    fn send(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Move(0, 0) | OutputEvent::Wheel(0) | OutputEvent::HWheel(0) => {}
+            OutputEvent::Move(dx, dy) => {
+                self.emit(&[
+                    InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, dx),
+                    InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_Y.0, dy),
+                ]);
+            }
+            OutputEvent::Wheel(delta) => self.wheel(delta, false),
+            OutputEvent::HWheel(delta) => self.wheel(delta, true),
+            OutputEvent::Button(button, down) => {
+                let key = match button {
+                    MouseButton::Left => Key::BTN_LEFT,
+                    MouseButton::Right => Key::BTN_RIGHT,
+                    MouseButton::Middle => Key::BTN_MIDDLE,
+                };
+                self.emit(&[InputEvent::new(EventType::KEY, key.code(), down as i32)]);
+            }
+            OutputEvent::Key(key, down) => {
+                self.emit(&[InputEvent::new(EventType::KEY, key.linux_code(), down as i32)]);
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::backend::{Button, GamepadState};
+use crate::output::{OutputEvent, OutputSink, WHEEL_DELTA};
+use crate::settings::{ScrollSource, Settings};
+
+const MAX_VAL: f32 = 32767.0;
+
+/// Turns a stick or the D-pad into wheel events, carrying the fractional
+/// wheel delta between ticks.
+#[derive(Debug, Default)]
+pub struct Scroller {
+    vertical: f32,
+    horizontal: f32,
+}
+
+impl Scroller {
+    /// Scroll direction in -1..=1 for (horizontal, vertical), up and right positive.
+    fn direction(settings: &Settings, source: ScrollSource, pad: &GamepadState) -> (f32, f32) {
+        match source {
+            ScrollSource::Off => (0.0, 0.0),
+            ScrollSource::RightStick | ScrollSource::LeftStick => {
+                let (x, y) = if source == ScrollSource::LeftStick { pad.left_stick } else { pad.right_stick };
+                let mag = (x * x + y * y).sqrt();
+                let threshold = settings.scroll_deadzone * MAX_VAL;
+                if mag == 0.0 || mag < threshold {
+                    return (0.0, 0.0);
+                }
+                let scaled = ((mag - threshold) / (MAX_VAL - threshold)).min(1.0);
+                (x / mag * scaled, y / mag * scaled)
+            }
+            ScrollSource::Dpad => {
+                let axis = |neg: Button, pos: Button| {
+                    pad.buttons.contains(pos) as i32 as f32 - pad.buttons.contains(neg) as i32 as f32
+                };
+                (axis(Button::DpadLeft, Button::DpadRight), axis(Button::DpadDown, Button::DpadUp))
+            }
+        }
+    }
+
+    /// `source` is passed separately since the active layer can override `settings.scroll_source`.
+    pub fn update(&mut self, settings: &Settings, source: ScrollSource, pad: &GamepadState, dt: f32, sink: &mut dyn OutputSink) {
+        let (h, v) = Self::direction(settings, source, pad);
+        if h == 0.0 && v == 0.0 {
+            // Don't let a partial notch from the last flick fire on the next one
+            self.reset();
+            return;
+        }
+
+        let units_per_sec = settings.scroll_speed * WHEEL_DELTA as f32;
+        self.horizontal += h * units_per_sec * dt;
+        self.vertical += v * units_per_sec * dt;
+
+        // Notched scrolling only sends whole detents, like a physical wheel
+        let step = if settings.scroll_smooth { 1.0 } else { WHEEL_DELTA as f32 };
+        let take = |acc: &mut f32| {
+            let whole = (*acc / step).trunc() * step;
+            *acc -= whole;
+            whole as i32
+        };
+
+        let vertical = take(&mut self.vertical);
+        if vertical != 0 {
+            sink.send(OutputEvent::Wheel(vertical));
+        }
+        let horizontal = take(&mut self.horizontal);
+        if horizontal != 0 {
+            sink.send(OutputEvent::HWheel(horizontal));
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
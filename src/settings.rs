@@ -3,21 +3,37 @@ use std::{
     path::{PathBuf},
     env,
     process::Command,
+    collections::VecDeque,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
 use directories::ProjectDirs;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use tao::event_loop::EventLoopProxy;
 
 use eframe::egui;
+use crate::backend::{self, Button, DeviceInfo};
+use crate::calibration::{self, StickMeasurement, StickRecorder};
+use crate::{curves, deadzone};
+use crate::ipc::Client;
+use crate::output::{Key, KeyChord, MouseButton};
+use crate::telemetry::{self, PadSample, RawSample, Telemetry};
 use crate::tray::TrayEvent;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum InputType {
-    #[default]
+    #[cfg_attr(not(target_os = "linux"), default)]
     XInput,
     DirectInputSingle,
     DirectInputDual,
+    #[cfg_attr(target_os = "linux", default)]
+    Evdev,
+    /// Raw HID reports from the Legion Go controllers
+    LegionGo,
+    /// DSU / cemuhook server at `dsu_host`:`dsu_port`
+    Dsu,
 }
 
 impl InputType {
@@ -26,6 +42,23 @@ impl InputType {
             InputType::XInput => "XInput (Xbox)",
             InputType::DirectInputSingle => "DirectInput (Single)",
             InputType::DirectInputDual => "DirectInput (Dual)",
+            InputType::Evdev => "evdev (Linux)",
+            InputType::LegionGo => "Legion Go (HID)",
+            InputType::Dsu => "DSU / cemuhook (UDP)",
+        }
+    }
+
+    /// Why this input type can't read anything on this system, or `None` if it can.
+    pub fn unavailable(&self) -> Option<&'static str> {
+        match self {
+            InputType::XInput | InputType::DirectInputSingle | InputType::DirectInputDual if !cfg!(windows) => {
+                Some("only works on Windows")
+            }
+            InputType::Evdev if !cfg!(target_os = "linux") => Some("only works on Linux"),
+            InputType::LegionGo if !cfg!(target_os = "linux") => {
+                Some("reads /dev/hidraw and only works on Linux; on Windows use DirectInput (Dual) for the detached controllers")
+            }
+            _ => None,
         }
     }
 }
@@ -50,6 +83,253 @@ impl DualStickPriority {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DeadzoneShape {
+    #[default]
+    Axial,
+    Radial,
+    ScaledRadial,
+    Hybrid,
+    Bowtie,
+}
+
+impl DeadzoneShape {
+    pub const ALL: [DeadzoneShape; 5] = [
+        DeadzoneShape::Axial,
+        DeadzoneShape::Radial,
+        DeadzoneShape::ScaledRadial,
+        DeadzoneShape::Hybrid,
+        DeadzoneShape::Bowtie,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeadzoneShape::Axial => "Axial (Square)",
+            DeadzoneShape::Radial => "Radial",
+            DeadzoneShape::ScaledRadial => "Scaled Radial",
+            DeadzoneShape::Hybrid => "Hybrid",
+            DeadzoneShape::Bowtie => "Bowtie",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    Power,
+    Exponential,
+    SCurve,
+    Custom,
+}
+
+impl ResponseCurve {
+    pub const ALL: [ResponseCurve; 5] = [
+        ResponseCurve::Linear,
+        ResponseCurve::Power,
+        ResponseCurve::Exponential,
+        ResponseCurve::SCurve,
+        ResponseCurve::Custom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::Power => "Power",
+            ResponseCurve::Exponential => "Exponential",
+            ResponseCurve::SCurve => "S-Curve",
+            ResponseCurve::Custom => "Custom",
+        }
+    }
+}
+
+fn default_curve_strength() -> f32 {
+    2.0
+}
+
+fn default_curve_points() -> [[f32; 2]; 2] {
+    [[0.5, 0.0], [0.75, 0.5]]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ButtonAction {
+    Mouse(MouseButton),
+    /// Held for as long as the controller button is, e.g. "Ctrl+C"
+    Keys(KeyChord),
+}
+
+impl ButtonAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ButtonAction::Mouse(button) => button.label(),
+            ButtonAction::Keys(_) => "Keyboard",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ButtonMapping {
+    pub button: Button,
+    /// Sent on press, or on a short tap when `hold` or `double_tap` is also set
+    pub action: ButtonAction,
+    /// Sent instead of `action` once the button has been down for `hold_time`
+    #[serde(default)]
+    pub hold: Option<ButtonAction>,
+    /// Sent instead of `action` when pressed again within `double_tap_time`
+    #[serde(default)]
+    pub double_tap: Option<ButtonAction>,
+    /// Clicked whenever the button comes up, after whatever else fired
+    #[serde(default)]
+    pub release: Option<ButtonAction>,
+}
+
+impl ButtonMapping {
+    pub fn new(button: Button, action: ButtonAction) -> Self {
+        Self { button, action, hold: None, double_tap: None, release: None }
+    }
+}
+
+fn default_button_mappings() -> Vec<ButtonMapping> {
+    vec![
+        ButtonMapping::new(Button::A, ButtonAction::Mouse(MouseButton::Left)),
+        ButtonMapping::new(Button::B, ButtonAction::Mouse(MouseButton::Right)),
+        ButtonMapping::new(Button::LeftStick, ButtonAction::Mouse(MouseButton::Middle)),
+    ]
+}
+
+fn default_hold_time() -> f32 {
+    0.4
+}
+
+fn default_double_tap_time() -> f32 {
+    0.25
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ScrollSource {
+    #[default]
+    Off,
+    RightStick,
+    Dpad,
+    /// Takes the left stick over from the cursor
+    LeftStick,
+}
+
+impl ScrollSource {
+    pub const ALL: [ScrollSource; 4] = [ScrollSource::Off, ScrollSource::RightStick, ScrollSource::Dpad, ScrollSource::LeftStick];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScrollSource::Off => "Off",
+            ScrollSource::RightStick => "Right Stick",
+            ScrollSource::Dpad => "D-pad / POV Hat",
+            ScrollSource::LeftStick => "Left Stick (no cursor)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PrecisionInput {
+    #[default]
+    Off,
+    /// Held `precision_button`
+    Button,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl PrecisionInput {
+    pub const ALL: [PrecisionInput; 4] = [
+        PrecisionInput::Off,
+        PrecisionInput::Button,
+        PrecisionInput::LeftTrigger,
+        PrecisionInput::RightTrigger,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PrecisionInput::Off => "Off",
+            PrecisionInput::Button => "Button",
+            PrecisionInput::LeftTrigger => "Left Trigger",
+            PrecisionInput::RightTrigger => "Right Trigger",
+        }
+    }
+}
+
+fn default_precision_button() -> Button {
+    Button::RightShoulder
+}
+
+fn default_precision_scale() -> f32 {
+    0.3
+}
+
+fn default_dsu_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_dsu_port() -> u16 {
+    26760
+}
+
+fn default_gyro_sensitivity() -> f32 {
+    10.0
+}
+
+fn default_gyro_smoothing() -> f32 {
+    5.0
+}
+
+fn default_gyro_tightening() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LayerActivation {
+    /// Active while the shift button is held
+    #[default]
+    Momentary,
+    /// Pressing the shift button turns the layer on and off
+    Toggle,
+}
+
+/// Alternate set of bindings switched in by a shift button. Only editable in
+/// config.toml for now.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    /// Shift button; never passed on to button mappings
+    pub button: Button,
+    #[serde(default)]
+    pub activation: LayerActivation,
+    /// Replace the base mappings entirely while the layer is active
+    #[serde(default)]
+    pub button_mappings: Vec<ButtonMapping>,
+    /// Overrides `scroll_source` while the layer is active
+    #[serde(default)]
+    pub scroll_source: Option<ScrollSource>,
+}
+
+fn default_scroll_deadzone() -> f32 {
+    0.2
+}
+
+fn default_scroll_speed() -> f32 {
+    10.0
+}
+
+fn default_acceleration_threshold() -> f32 {
+    0.9
+}
+
+fn default_acceleration_ramp_time() -> f32 {
+    1.0
+}
+
+fn default_acceleration_max() -> f32 {
+    3.0
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     pub enabled: bool,
@@ -61,9 +341,93 @@ pub struct Settings {
     pub sensitivity: f32,
     pub deadzone: f32,
     #[serde(default)]
+    pub outer_deadzone: f32,
+    #[serde(default)]
+    pub deadzone_shape: DeadzoneShape,
+    #[serde(default)]
+    pub response_curve: ResponseCurve,
+    /// Exponent for `Power`, steepness for `Exponential` and `SCurve`
+    #[serde(default = "default_curve_strength")]
+    pub curve_strength: f32,
+    /// Control points of the `Custom` cubic bezier, as in CSS cubic-bezier(x1, y1, x2, y2)
+    #[serde(default = "default_curve_points")]
+    pub curve_points: [[f32; 2]; 2],
+    #[serde(default)]
+    pub acceleration: bool,
+    /// Deflection (0-1, after deadzone) the stick has to stay above to accelerate
+    #[serde(default = "default_acceleration_threshold")]
+    pub acceleration_threshold: f32,
+    /// Seconds to go from 1x to `acceleration_max`
+    #[serde(default = "default_acceleration_ramp_time")]
+    pub acceleration_ramp_time: f32,
+    #[serde(default = "default_acceleration_max")]
+    pub acceleration_max: f32,
+    #[serde(default = "default_button_mappings")]
+    pub button_mappings: Vec<ButtonMapping>,
+    /// Seconds a button has to stay down before its hold action fires
+    #[serde(default = "default_hold_time")]
+    pub hold_time: f32,
+    /// Seconds allowed between the release of a tap and the second press
+    #[serde(default = "default_double_tap_time")]
+    pub double_tap_time: f32,
+    #[serde(default)]
+    pub scroll_source: ScrollSource,
+    #[serde(default = "default_scroll_deadzone")]
+    pub scroll_deadzone: f32,
+    /// Wheel notches per second at full deflection
+    #[serde(default = "default_scroll_speed")]
+    pub scroll_speed: f32,
+    /// Send fractional notches instead of whole detents
+    #[serde(default)]
+    pub scroll_smooth: bool,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub precision: PrecisionInput,
+    /// Button used when `precision` is `Button`; never passed on to button mappings
+    #[serde(default = "default_precision_button")]
+    pub precision_button: Button,
+    /// Speed multiplier while precision mode is fully engaged
+    #[serde(default = "default_precision_scale")]
+    pub precision_scale: f32,
+    /// Scale speed with how far the trigger is pulled instead of switching at half-way
+    #[serde(default)]
+    pub precision_analog: bool,
+    /// Move the cursor by turning controllers that report motion, added to the stick
+    #[serde(default)]
+    pub gyro: bool,
+    /// Cursor pixels per degree of rotation
+    #[serde(default = "default_gyro_sensitivity")]
+    pub gyro_sensitivity: f32,
+    /// Rotation slower than this (deg/s) is smoothed to hide sensor noise
+    #[serde(default = "default_gyro_smoothing")]
+    pub gyro_smoothing: f32,
+    /// Rotation slower than this (deg/s) is scaled down so a resting controller doesn't drift
+    #[serde(default = "default_gyro_tightening")]
+    pub gyro_tightening: f32,
+    /// Pauses gyro while held, to re-center like lifting a mouse; never passed on to button mappings
+    #[serde(default)]
+    pub gyro_ratchet: Option<Button>,
+    #[serde(default)]
     pub input_type: InputType,
     #[serde(default)]
     pub dual_stick_priority: DualStickPriority,
+    /// Controller used as stick 1 (or the only stick), by `DeviceInfo::key`. Any controller when unset
+    #[serde(default)]
+    pub stick1_device: Option<String>,
+    /// Controller used as stick 2 in dual mode and for the Legion Go, by `DeviceInfo::key`
+    #[serde(default)]
+    pub stick2_device: Option<String>,
+    /// Only read the XInput controller at this user index (0-3) instead of combining all of them
+    #[serde(default)]
+    pub xinput_user_index: Option<u32>,
+    #[serde(default = "default_dsu_host")]
+    pub dsu_host: String,
+    #[serde(default = "default_dsu_port")]
+    pub dsu_port: u16,
+    /// Layout the file was saved in, see `migrate`. Files from before it was added read as 0
+    #[serde(default)]
+    pub config_version: u32,
 }
 
 impl Default for Settings {
@@ -75,18 +439,73 @@ impl Default for Settings {
             swap_axes: false,
             sensitivity: 1.0,
             deadzone: 0.075,
-            input_type: InputType::XInput,
+            outer_deadzone: 0.0,
+            deadzone_shape: DeadzoneShape::Axial,
+            response_curve: ResponseCurve::Linear,
+            curve_strength: default_curve_strength(),
+            curve_points: default_curve_points(),
+            acceleration: false,
+            acceleration_threshold: default_acceleration_threshold(),
+            acceleration_ramp_time: default_acceleration_ramp_time(),
+            acceleration_max: default_acceleration_max(),
+            button_mappings: default_button_mappings(),
+            hold_time: default_hold_time(),
+            double_tap_time: default_double_tap_time(),
+            scroll_source: ScrollSource::Off,
+            scroll_deadzone: default_scroll_deadzone(),
+            scroll_speed: default_scroll_speed(),
+            scroll_smooth: false,
+            layers: Vec::new(),
+            precision: PrecisionInput::Off,
+            precision_button: default_precision_button(),
+            precision_scale: default_precision_scale(),
+            precision_analog: false,
+            gyro: false,
+            gyro_sensitivity: default_gyro_sensitivity(),
+            gyro_smoothing: default_gyro_smoothing(),
+            gyro_tightening: default_gyro_tightening(),
+            gyro_ratchet: None,
+            input_type: InputType::default(),
             dual_stick_priority: DualStickPriority::Stick1First,
+            stick1_device: None,
+            stick2_device: None,
+            xinput_user_index: None,
+            dsu_host: default_dsu_host(),
+            dsu_port: default_dsu_port(),
+            config_version: CONFIG_VERSION,
         }
     }
 }
 
-fn config_path() -> Result<PathBuf> {
+/// Bumped whenever settings saved by an older version need updating in `migrate`.
+const CONFIG_VERSION: u32 = 1;
+
+/// Updates settings saved by an older version to mean the same thing now.
+/// Returns whether anything had to change.
+fn migrate(s: &mut Settings) -> bool {
+    if s.config_version >= CONFIG_VERSION {
+        return false;
+    }
+    // The DirectInput left stick used to report Y down, so invert_y did the
+    // opposite there; it's up-positive like every other backend now
+    if s.config_version < 1 && matches!(s.input_type, InputType::DirectInputSingle | InputType::DirectInputDual) {
+        s.invert_y = !s.invert_y;
+    }
+    s.config_version = CONFIG_VERSION;
+    true
+}
+
+/// Directory holding config.toml and the other files the app keeps.
+pub fn config_dir() -> Result<PathBuf> {
     let proj = ProjectDirs::from("com", "AlanZ", "ControllerTray")
         .context("Failed to get ProjectDirs")?;
     let dir = proj.config_dir();
     fs::create_dir_all(dir).ok();
-    Ok(dir.join("config.toml"))
+    Ok(dir.to_path_buf())
+}
+
+fn config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
 }
 
 pub fn load_settings() -> Result<Settings> {
@@ -97,10 +516,94 @@ pub fn load_settings() -> Result<Settings> {
         return Ok(s);
     }
     let txt = fs::read_to_string(&path).context("Reading config.toml")?;
-    let s: Settings = toml::from_str(&txt).context("Parsing config.toml")?;
+    let mut s: Settings = toml::from_str(&txt).context("Parsing config.toml")?;
+    if migrate(&mut s) {
+        save_settings(&s)?;
+    }
     Ok(s)
 }
 
+/// Reads `profiles/<name>.toml` from the config folder, a settings file laid
+/// out like config.toml.
+pub fn load_profile(name: &str) -> Result<Settings> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("Invalid profile name: {}", name);
+    }
+    let path = config_dir()?.join("profiles").join(format!("{}.toml", name));
+    let txt = fs::read_to_string(&path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut s: Settings = toml::from_str(&txt).with_context(|| format!("Parsing {}", path.display()))?;
+    migrate(&mut s);
+    Ok(s)
+}
+
+/// Range the settings window's slider allows for a number setting, by its
+/// name in config.toml. `curve` picks the range of `curve_strength`, which
+/// means something different for each curve.
+fn slider_range(name: &str, curve: ResponseCurve) -> Option<RangeInclusive<f32>> {
+    let range = match name {
+        "sensitivity" => 0.01..=2.0,
+        "deadzone" => 0.0..=0.5,
+        "outer_deadzone" => 0.0..=0.3,
+        "curve_strength" => match curve {
+            ResponseCurve::Power => 0.2..=5.0,
+            ResponseCurve::SCurve => 1.0..=5.0,
+            // Unused by the other curves, any value the sliders allow will do
+            ResponseCurve::Exponential | ResponseCurve::Linear | ResponseCurve::Custom => 0.0..=10.0,
+        },
+        "acceleration_threshold" => 0.5..=1.0,
+        "acceleration_ramp_time" => 0.1..=5.0,
+        "acceleration_max" => 1.0..=10.0,
+        "precision_scale" => 0.05..=1.0,
+        "gyro_sensitivity" => 1.0..=40.0,
+        "gyro_smoothing" => 0.0..=20.0,
+        "gyro_tightening" => 0.0..=10.0,
+        "scroll_speed" => 1.0..=40.0,
+        "scroll_deadzone" => 0.0..=0.5,
+        "hold_time" => 0.1..=1.5,
+        "double_tap_time" => 0.1..=1.0,
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Returns `s` with the setting called `name` in config.toml changed to
+/// `value`, written as in config.toml; strings don't need quotes. Numbers
+/// are held to the range the settings window allows.
+pub fn set_field(s: &Settings, name: &str, value: &str) -> Result<Settings> {
+    let value = toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+    let toml::Value::Table(mut table) = toml::Value::try_from(s).context("Serializing config")? else {
+        bail!("Settings didn't serialize to a table");
+    };
+    table.insert(name.to_string(), value);
+    let updated: Settings = toml::Value::Table(table).try_into().with_context(|| format!("Invalid value for {}", name))?;
+    // Unknown names are ignored when deserializing, so check it stuck
+    let known = matches!(toml::Value::try_from(&updated), Ok(toml::Value::Table(t)) if t.contains_key(name));
+    if !known {
+        bail!("Unknown setting: {}", name);
+    }
+    if let Some(range) = slider_range(name, updated.response_curve) {
+        let number = toml::Value::try_from(&updated).ok().and_then(|t| t.get(name).and_then(toml::Value::as_float));
+        // NaN is outside every range
+        if !number.is_some_and(|n| range.contains(&(n as f32))) {
+            bail!("{} must be between {} and {}", name, range.start(), range.end());
+        }
+    }
+    if name == "input_type" {
+        if let Some(reason) = updated.input_type.unavailable() {
+            bail!("{} {}", updated.input_type.label(), reason);
+        }
+    }
+    Ok(updated)
+}
+
+/// When config.toml was last written, `None` if it can't be read.
+fn config_modified() -> Option<SystemTime> {
+    fs::metadata(config_path().ok()?).and_then(|m| m.modified()).ok()
+}
+
 fn save_settings(s: &Settings) -> Result<()> {
     let path = config_path()?;
     let txt = toml::to_string_pretty(s).context("Serializing config")?;
@@ -119,25 +622,573 @@ pub fn spawn_settings_window(proxy: EventLoopProxy<TrayEvent>) -> anyhow::Result
         .spawn()
         .context("failed to spawn settings window")?;
 
+    let before = config_modified();
     std::thread::spawn(move || {
         let _ = child.wait(); // blocks until settings window closes
-        let _ = proxy.send_event(TrayEvent::ReloadSettings);
+        let saved = config_modified() != before;
+        let _ = proxy.send_event(TrayEvent::SettingsClosed { saved });
     });
     Ok(())
 }
 
+/// Which of a mapping's actions the button mapping grid is editing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+enum MappingSlot {
+    #[default]
+    Press,
+    Hold,
+    DoubleTap,
+    Release,
+}
+
+impl MappingSlot {
+    const ALL: [MappingSlot; 4] = [MappingSlot::Press, MappingSlot::Hold, MappingSlot::DoubleTap, MappingSlot::Release];
+
+    fn label(&self) -> &'static str {
+        match self {
+            MappingSlot::Press => "Press / Tap",
+            MappingSlot::Hold => "Hold",
+            MappingSlot::DoubleTap => "Double Tap",
+            MappingSlot::Release => "Release",
+        }
+    }
+}
+
+/// One row per controller button with the action it sends for the selected gesture.
+fn button_mapping_editor(ui: &mut egui::Ui, settings: &mut Settings) {
+    let slot_id = egui::Id::new("mapping_slot");
+    let mut slot = ui.data_mut(|d| d.get_temp::<MappingSlot>(slot_id)).unwrap_or_default();
+    ui.horizontal(|ui| {
+        for s in MappingSlot::ALL {
+            ui.selectable_value(&mut slot, s, s.label());
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(slot_id, slot));
+
+    egui::Grid::new("button_mappings").num_columns(3).show(ui, |ui| {
+        for button in Button::ALL {
+            ui.label(button.label());
+
+            let index = settings.button_mappings.iter().position(|m| m.button == button);
+            let id = egui::Id::new(("button_mapping", button, slot));
+            match (slot, index) {
+                (MappingSlot::Press, _) => {
+                    let current = index.map(|i| settings.button_mappings[i].action.clone());
+                    let mut selected = current.clone();
+                    action_picker(ui, id, &mut selected);
+
+                    if selected != current {
+                        match (index, selected) {
+                            (Some(i), Some(action)) => settings.button_mappings[i].action = action,
+                            (Some(i), None) => {
+                                settings.button_mappings.remove(i);
+                            }
+                            (None, Some(action)) => settings.button_mappings.push(ButtonMapping::new(button, action)),
+                            (None, None) => {}
+                        }
+                    }
+                }
+                (_, Some(i)) => {
+                    let mapping = &mut settings.button_mappings[i];
+                    let action = match slot {
+                        MappingSlot::Hold => &mut mapping.hold,
+                        MappingSlot::DoubleTap => &mut mapping.double_tap,
+                        _ => &mut mapping.release,
+                    };
+                    action_picker(ui, id, action);
+                }
+                (_, None) => {
+                    ui.add_enabled(false, egui::Label::new("Needs a press action"));
+                    ui.label("");
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Combo box for one action plus, for key chords, the chord text. Fills two grid cells.
+fn action_picker(ui: &mut egui::Ui, id: egui::Id, selected: &mut Option<ButtonAction>) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(selected.as_ref().map_or("None", |a| a.label()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(selected, None, "None");
+            for mouse in MouseButton::ALL {
+                let action = ButtonAction::Mouse(mouse);
+                let label = action.label();
+                ui.selectable_value(selected, Some(action), label);
+            }
+            let is_keys = matches!(selected, Some(ButtonAction::Keys(_)));
+            if ui.selectable_label(is_keys, "Keyboard").clicked() && !is_keys {
+                *selected = Some(ButtonAction::Keys(KeyChord(vec![Key::Space])));
+            }
+        });
+
+    let text_id = id.with("chord_text");
+    if let Some(ButtonAction::Keys(chord)) = selected {
+        chord_field(ui, text_id, chord);
+    } else {
+        ui.data_mut(|d| d.remove::<String>(text_id));
+        ui.label("");
+    }
+}
+
+/// Text box for a key chord. The text is kept separately while it doesn't
+/// parse, so half-typed chords aren't thrown away.
+fn chord_field(ui: &mut egui::Ui, id: egui::Id, chord: &mut KeyChord) {
+    let mut text = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_else(|| chord.to_string());
+
+    ui.horizontal(|ui| {
+        let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(140.0).hint_text("Ctrl+Shift+Esc"));
+        match text.parse::<KeyChord>() {
+            Ok(parsed) => *chord = parsed,
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        }
+        if response.lost_focus() && text.parse::<KeyChord>().is_ok() {
+            // Normalise spelling once editing is done, e.g. "ctrl+c" to "Ctrl+C"
+            text = chord.to_string();
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, text));
+}
+
+/// Combo box pinning a stick to one controller, by `DeviceInfo::key`.
+fn device_picker(ui: &mut egui::Ui, id: &str, selected: &mut Option<String>, devices: &[DeviceInfo]) {
+    let text = match selected.as_deref() {
+        None => "Any".to_string(),
+        Some(key) => match devices.iter().find(|d| d.key == key) {
+            Some(device) => device.name.clone(),
+            None => format!("{} (not connected)", key),
+        },
+    };
+    egui::ComboBox::from_id_source(id)
+        .selected_text(text)
+        .width(220.0)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(selected, None, "Any");
+            for device in devices {
+                ui.selectable_value(selected, Some(device.key.clone()), &device.name)
+                    .on_hover_text(&device.key);
+            }
+        });
+}
+
+/// How long the stick has to be left alone in the first wizard step
+const REST_TIME: Duration = Duration::from_secs(2);
+/// Fraction of directions the stick has to reach before the wizard can finish
+const MIN_COVERAGE: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum WizardStick {
+    #[default]
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum WizardStep {
+    #[default]
+    Idle,
+    /// Stick left centered, sampled until `until`
+    Rest { until: Instant },
+    /// Stick rotated around its edge until the user finishes
+    Rotate,
+    Done,
+}
+
+/// Calibration wizard state. Readings come from the tray rather than opening
+/// the controller here, so it measures exactly what the tray sees.
+#[derive(Debug, Default)]
+struct CalibrationWizard {
+    /// `DeviceInfo::key` of the controller being calibrated
+    device: Option<String>,
+    stick: WizardStick,
+    step: WizardStep,
+    recorder: StickRecorder,
+    measurement: Option<StickMeasurement>,
+    status: Option<String>,
+}
+
+impl CalibrationWizard {
+    fn running(&self) -> bool {
+        matches!(self.step, WizardStep::Rest { .. } | WizardStep::Rotate)
+    }
+
+    /// Feeds one live reading to the current step.
+    fn record(&mut self, sample: &RawSample, now: Instant) {
+        if self.device.as_deref() != Some(sample.key.as_str()) {
+            return;
+        }
+        let stick = match self.stick {
+            WizardStick::Left => sample.left_stick,
+            WizardStick::Right => sample.right_stick,
+        };
+        match self.step {
+            WizardStep::Rest { until } if now < until => self.recorder.record_rest(stick),
+            WizardStep::Rest { .. } => self.step = WizardStep::Rotate,
+            WizardStep::Rotate => self.recorder.record_edge(stick),
+            WizardStep::Idle | WizardStep::Done => {}
+        }
+    }
+
+    /// Stores the measurement in calibration.toml, next to any other stick's.
+    fn save(&self, measurement: &StickMeasurement) -> Result<()> {
+        let key = self.device.clone().context("No controller selected")?;
+        // A broken file is already ignored by the tray, so it's replaced
+        let mut calibrations = calibration::load_calibrations_or_default();
+        let device = calibrations.devices.entry(key).or_default();
+        match self.stick {
+            WizardStick::Left => device.left_stick = measurement.calibration,
+            WizardStick::Right => device.right_stick = measurement.calibration,
+        }
+        calibration::save_calibrations(&calibrations)
+    }
+
+    /// Drops the saved calibration of the selected controller.
+    fn reset(&self) -> Result<()> {
+        let Some(key) = &self.device else {
+            return Ok(());
+        };
+        let mut calibrations = calibration::load_calibrations_or_default();
+        calibrations.devices.remove(key);
+        calibration::save_calibrations(&calibrations)
+    }
+}
+
+/// Has the tray reload calibration.toml after `saved` changed it, and
+/// returns the status line to show.
+fn apply_calibration(tray: &mut Option<Client>, saved: Result<()>, done: &str) -> String {
+    if let Err(e) = saved {
+        return format!("Failed to save: {:#}", e);
+    }
+    match tray.as_mut().map(|tray| tray.request("calibration", None)) {
+        Some(Ok(_)) => format!("{}, applied now.", done),
+        Some(Err(e)) => format!("{}, but the tray failed to reload it: {:#}", done, e),
+        None => format!("{}, applied when the tray starts.", done),
+    }
+}
+
+/// Walks through measuring a stick at rest and around its edge, then saves
+/// the result and has the tray apply it.
+fn calibration_wizard(ui: &mut egui::Ui, wizard: &mut CalibrationWizard, live: &[RawSample], tray: &mut Option<Client>) {
+    if live.is_empty() {
+        ui.label("Waiting for controller readings from the tray. Open Settings from the tray icon with a controller connected.");
+        return;
+    }
+    if !wizard.device.as_ref().is_some_and(|key| live.iter().any(|s| s.key == *key)) {
+        wizard.device = Some(live[0].key.clone());
+    }
+
+    ui.add_enabled_ui(!wizard.running(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Controller:");
+            let name = live.iter().find(|s| wizard.device.as_ref() == Some(&s.key)).map_or("", |s| s.name.as_str());
+            egui::ComboBox::from_id_source("calibration_device")
+                .selected_text(name)
+                .show_ui(ui, |ui| {
+                    for sample in live {
+                        ui.selectable_value(&mut wizard.device, Some(sample.key.clone()), &sample.name);
+                    }
+                });
+            ui.selectable_value(&mut wizard.stick, WizardStick::Left, "Left Stick");
+            ui.selectable_value(&mut wizard.stick, WizardStick::Right, "Right Stick");
+        });
+    });
+
+    if let Some(sample) = live.iter().find(|s| wizard.device.as_ref() == Some(&s.key)) {
+        let (x, y) = match wizard.stick {
+            WizardStick::Left => sample.left_stick,
+            WizardStick::Right => sample.right_stick,
+        };
+        ui.label(format!("Raw: {:.0}, {:.0}", x, y));
+    }
+
+    match wizard.step {
+        WizardStep::Idle => {
+            ui.horizontal(|ui| {
+                if ui.button("Start").clicked() {
+                    wizard.recorder = StickRecorder::default();
+                    wizard.measurement = None;
+                    wizard.status = None;
+                    wizard.step = WizardStep::Rest { until: Instant::now() + REST_TIME };
+                }
+                if ui.button("Reset Controller").on_hover_text("Remove the saved calibration for both sticks").clicked() {
+                    wizard.status = Some(apply_calibration(tray, wizard.reset(), "Calibration removed"));
+                }
+            });
+        }
+        WizardStep::Rest { until } => {
+            ui.label("Step 1 of 2: leave the stick centered and don't touch it.");
+            let left = until.saturating_duration_since(Instant::now()).as_secs_f32();
+            ui.add(egui::ProgressBar::new(1.0 - left / REST_TIME.as_secs_f32()));
+        }
+        WizardStep::Rotate => {
+            ui.label("Step 2 of 2: rotate the stick slowly around its edge a few times.");
+            let coverage = wizard.recorder.coverage();
+            ui.add(egui::ProgressBar::new(coverage).text(format!("{:.0}% of directions", coverage * 100.0)));
+            ui.horizontal(|ui| {
+                if ui.add_enabled(coverage >= MIN_COVERAGE, egui::Button::new("Finish")).clicked() {
+                    wizard.measurement = wizard.recorder.finish();
+                    wizard.step = WizardStep::Done;
+                }
+                if ui.button("Cancel").clicked() {
+                    wizard.step = WizardStep::Idle;
+                }
+            });
+        }
+        WizardStep::Done => {
+            if let Some(m) = wizard.measurement {
+                ui.label(format!("Drift at rest: {:+.1}% X, {:+.1}% Y", m.drift.0 * 100.0, m.drift.1 * 100.0));
+                ui.label(format!("Noise at rest: {:.1}%", m.noise * 100.0));
+                ui.label(format!("Circularity error: {:.1}%", m.circularity_error * 100.0));
+            }
+            ui.horizontal(|ui| {
+                if let Some(m) = wizard.measurement {
+                    if ui.button("Save Calibration").clicked() {
+                        wizard.status = Some(apply_calibration(tray, wizard.save(&m), "Saved"));
+                        wizard.step = WizardStep::Idle;
+                    }
+                }
+                if ui.button("Discard").clicked() {
+                    wizard.step = WizardStep::Idle;
+                }
+            });
+        }
+    }
+
+    if let Some(status) = &wizard.status {
+        ui.label(status);
+    }
+}
+
+/// Full stick deflection in the XInput range the backends report
+const STICK_MAX: f32 = 32767.0;
+/// Pad readings older than this are from a controller that's gone
+const PAD_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Square plot of stick positions, up positive. With `overlay` the inner and
+/// outer deadzone are drawn, as squares for `Axial` and circles otherwise.
+fn stick_plot(ui: &mut egui::Ui, overlay: Option<&Settings>, dots: &[((f32, f32), egui::Color32)]) {
+    let (response, painter) = ui.allocate_painter(egui::vec2(140.0, 140.0), egui::Sense::hover());
+    let rect = response.rect.shrink(4.0);
+    let center = rect.center();
+    let radius = rect.width() / 2.0;
+    let to_screen = |(x, y): (f32, f32)| egui::pos2(center.x + x / STICK_MAX * radius, center.y - y / STICK_MAX * radius);
+
+    let grid = egui::Stroke::new(1.0, ui.visuals().weak_text_color());
+    painter.rect_stroke(rect, 0.0, grid);
+    painter.circle_stroke(center, radius, grid);
+    painter.line_segment([egui::pos2(rect.left(), center.y), egui::pos2(rect.right(), center.y)], grid);
+    painter.line_segment([egui::pos2(center.x, rect.top()), egui::pos2(center.x, rect.bottom())], grid);
+
+    if let Some(settings) = overlay {
+        let zone = egui::Stroke::new(1.0, egui::Color32::from_rgb(230, 140, 60));
+        let mut zones = vec![settings.deadzone * radius];
+        if settings.outer_deadzone > 0.0 {
+            zones.push((1.0 - settings.outer_deadzone) * radius);
+        }
+        for r in zones {
+            if settings.deadzone_shape == DeadzoneShape::Axial {
+                painter.rect_stroke(egui::Rect::from_center_size(center, egui::vec2(r * 2.0, r * 2.0)), 0.0, zone);
+            } else {
+                painter.circle_stroke(center, r, zone);
+            }
+        }
+    }
+
+    for &(pos, color) in dots {
+        painter.circle_filled(to_screen(pos), 4.0, color);
+    }
+}
+
+/// Live view of what the tray reads: the stick before and after the deadzone
+/// and curve, buttons, triggers, the touchpad and the cursor speed that came out. The
+/// deadzone and curve shown are the ones being edited, not yet saved ones.
+fn input_tester(ui: &mut egui::Ui, settings: &Settings, pad: Option<&PadSample>, live: &[RawSample]) {
+    ui.ctx().request_repaint_after(Duration::from_millis(33));
+    let Some(pad) = pad else {
+        ui.label("Waiting for controller readings from the tray. Open Settings from the tray icon with a controller connected.");
+        return;
+    };
+
+    let stick = if settings.swap_axes { (pad.left_stick.1, pad.left_stick.0) } else { pad.left_stick };
+    let shaped = deadzone::apply(settings.deadzone_shape, stick, settings.deadzone, settings.outer_deadzone);
+    let shaped = curves::apply(settings, shaped);
+
+    ui.horizontal(|ui| {
+        ui.vertical(|ui| {
+            ui.label("Input");
+            // Uncalibrated readings of each controller, behind the stick the tray uses
+            let mut dots: Vec<((f32, f32), egui::Color32)> =
+                live.iter().map(|s| (s.left_stick, egui::Color32::from_gray(120))).collect();
+            dots.push((stick, egui::Color32::LIGHT_BLUE));
+            stick_plot(ui, Some(settings), &dots);
+        });
+        ui.vertical(|ui| {
+            ui.label("After Deadzone & Curve");
+            stick_plot(ui, None, &[(shaped, egui::Color32::LIGHT_GREEN)]);
+        });
+    });
+
+    ui.horizontal(|ui| {
+        for (label, value) in [("LT", pad.left_trigger), ("RT", pad.right_trigger)] {
+            ui.add(egui::ProgressBar::new(value).desired_width(120.0).text(format!("{} {:.2}", label, value)));
+        }
+    });
+
+    egui::Grid::new("tester_buttons").num_columns(4).show(ui, |ui| {
+        for (i, button) in Button::ALL.iter().enumerate() {
+            ui.add(egui::SelectableLabel::new(pad.buttons.contains(*button), button.label()));
+            if i % 4 == 3 {
+                ui.end_row();
+            }
+        }
+    });
+
+    ui.label(format!("Cursor: {:.0}, {:.0} px/s", pad.velocity.0, pad.velocity.1));
+    if let Some((x, y)) = pad.touchpad {
+        ui.label(format!("Touchpad: {:.2}, {:.2}", x, y));
+    }
+}
+
+/// Plots the response curve; for `Custom` the two bezier handles can be dragged.
+fn curve_editor(ui: &mut egui::Ui, settings: &mut Settings) {
+    let size = egui::vec2(ui.available_width().min(240.0), 160.0);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect.shrink(6.0);
+
+    let to_screen = |p: [f32; 2]| egui::pos2(rect.left() + p[0] * rect.width(), rect.bottom() - p[1] * rect.height());
+    let from_screen = |p: egui::Pos2| [
+        ((p.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+        ((rect.bottom() - p.y) / rect.height()).clamp(0.0, 1.0),
+    ];
+
+    let grid = egui::Stroke::new(1.0, ui.visuals().weak_text_color());
+    painter.rect_stroke(rect, 0.0, grid);
+    painter.line_segment([to_screen([0.0, 0.0]), to_screen([1.0, 1.0])], grid);
+
+    if settings.response_curve == ResponseCurve::Custom {
+        let handle_stroke = egui::Stroke::new(1.0, ui.visuals().text_color());
+        painter.line_segment([to_screen([0.0, 0.0]), to_screen(settings.curve_points[0])], handle_stroke);
+        painter.line_segment([to_screen([1.0, 1.0]), to_screen(settings.curve_points[1])], handle_stroke);
+
+        for (i, point) in settings.curve_points.iter_mut().enumerate() {
+            let center = to_screen(*point);
+            let handle = ui.interact(
+                egui::Rect::from_center_size(center, egui::vec2(14.0, 14.0)),
+                response.id.with(i),
+                egui::Sense::drag(),
+            );
+            if handle.dragged() {
+                *point = from_screen(center + handle.drag_delta());
+            }
+            painter.circle_filled(to_screen(*point), 5.0, egui::Color32::LIGHT_BLUE);
+        }
+    }
+
+    let points: Vec<egui::Pos2> = (0..=64)
+        .map(|i| {
+            let x = i as f32 / 64.0;
+            let y = curves::evaluate(settings.response_curve, settings.curve_strength, &settings.curve_points, x);
+            to_screen([x, y])
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(2.0, egui::Color32::LIGHT_BLUE)));
+}
+
 pub fn run_settings_window() -> Result<()> {
     let mut settings = load_settings().unwrap_or_default();
     
     struct App {
         settings: Settings,
         saved: bool,
+        /// Connection to the running tray, `None` when there isn't one and
+        /// changes only apply once saved
+        tray: Option<Client>,
+        /// `settings` as last sent to the tray
+        pushed: String,
+        /// Controllers attached for `devices_for`, as the tray sees them or
+        /// listed with a backend of our own
+        devices: Vec<DeviceInfo>,
+        devices_for: Option<InputType>,
+        /// Readings streamed by the tray, drained every frame
+        live_queue: Arc<Mutex<VecDeque<Telemetry>>>,
+        /// Latest reading of each controller the tray has open
+        live: Vec<RawSample>,
+        /// Last pad reading and when it arrived
+        pad: Option<(PadSample, Instant)>,
+        wizard: CalibrationWizard,
+        /// Set by Close (No Save), after which the unsaved edits are never
+        /// pushed again
+        closing: bool,
+    }
+
+    impl App {
+        /// Sends `settings` to the tray if they changed since the last time.
+        fn push_settings(&mut self, settings: &Settings) {
+            let Some(tray) = &mut self.tray else {
+                return;
+            };
+            let Ok(text) = toml::to_string(settings) else {
+                return;
+            };
+            if text == self.pushed {
+                return;
+            }
+            match tray.push_settings(settings) {
+                Ok(()) => self.pushed = text,
+                Err(e) => {
+                    println!("Lost the tray, changes apply once saved: {:#}", e);
+                    self.tray = None;
+                }
+            }
+        }
+
+        fn list_devices(&mut self) -> Vec<DeviceInfo> {
+            let Some(tray) = &mut self.tray else {
+                return backend::open_backend(&self.settings).map(|mut b| b.enumerate()).unwrap_or_default();
+            };
+            match tray.devices() {
+                Ok(devices) => devices.into_iter().map(|device| device.info).collect(),
+                Err(e) => {
+                    println!("Failed to list controllers: {:#}", e);
+                    Vec::new()
+                }
+            }
+        }
     }
 
     impl eframe::App for App {
         fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
             use eframe::egui;
 
+            let messages: Vec<Telemetry> = self.live_queue.lock().unwrap().drain(..).collect();
+            let now = Instant::now();
+            for message in messages {
+                match message {
+                    Telemetry::Raw(sample) => {
+                        self.wizard.record(&sample, now);
+                        match self.live.iter_mut().find(|s| s.key == sample.key) {
+                            Some(latest) => *latest = sample,
+                            None => self.live.push(sample),
+                        }
+                    }
+                    Telemetry::Pad(pad) => self.pad = Some((pad, now)),
+                }
+            }
+            if self.pad.is_some_and(|(_, at)| now.duration_since(at) > PAD_TIMEOUT) {
+                self.pad = None;
+                self.live.clear();
+            }
+            if self.wizard.running() {
+                ctx.request_repaint_after(Duration::from_millis(16));
+            }
+
+            // Same ranges `ctl set` is held to
+            let curve = self.settings.response_curve;
+            let slider = |name: &str| slider_range(name, curve).expect("every slider has a range");
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.heading("Controller Tray Settings");
                 ui.separator();
@@ -154,13 +1205,73 @@ pub fn run_settings_window() -> Result<()> {
                     egui::ComboBox::from_id_source("input_type")
                         .selected_text(self.settings.input_type.label())
                         .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut self.settings.input_type, InputType::XInput, InputType::XInput.label());
-                            ui.selectable_value(&mut self.settings.input_type, InputType::DirectInputSingle, InputType::DirectInputSingle.label());
-                            ui.selectable_value(&mut self.settings.input_type, InputType::DirectInputDual, InputType::DirectInputDual.label());
+                            #[cfg(windows)]
+                            {
+                                ui.selectable_value(&mut self.settings.input_type, InputType::XInput, InputType::XInput.label());
+                                ui.selectable_value(&mut self.settings.input_type, InputType::DirectInputSingle, InputType::DirectInputSingle.label());
+                                ui.selectable_value(&mut self.settings.input_type, InputType::DirectInputDual, InputType::DirectInputDual.label());
+                            }
+                            #[cfg(target_os = "linux")]
+                            {
+                                ui.selectable_value(&mut self.settings.input_type, InputType::Evdev, InputType::Evdev.label());
+                                ui.selectable_value(&mut self.settings.input_type, InputType::LegionGo, InputType::LegionGo.label());
+                            }
+                            ui.selectable_value(&mut self.settings.input_type, InputType::Dsu, InputType::Dsu.label());
                         });
                 });
+                // Only reachable through a config.toml or profile from another system
+                if let Some(reason) = self.settings.input_type.unavailable() {
+                    ui.colored_label(egui::Color32::YELLOW, format!("{} {}.", self.settings.input_type.label(), reason));
+                }
 
-                if self.settings.input_type == InputType::DirectInputDual {
+                if self.settings.input_type == InputType::Dsu {
+                    ui.horizontal(|ui| {
+                        ui.label("DSU Server:");
+                        ui.add(egui::TextEdit::singleline(&mut self.settings.dsu_host).desired_width(120.0));
+                        ui.label("Port:");
+                        ui.add(egui::DragValue::new(&mut self.settings.dsu_port));
+                    });
+                }
+
+                if self.devices_for != Some(self.settings.input_type) {
+                    self.devices_for = Some(self.settings.input_type);
+                    // The tray lists what its backend sees, so switch it over first
+                    self.push_settings(&self.settings.clone());
+                    self.devices = self.list_devices();
+                }
+                let dual = self.settings.input_type == InputType::DirectInputDual;
+                // Each Legion Go controller is listed on its own and both are read
+                let two_devices = dual || self.settings.input_type == InputType::LegionGo;
+                if self.settings.input_type == InputType::XInput {
+                    ui.horizontal(|ui| {
+                        ui.label("User Index:");
+                        egui::ComboBox::from_id_source("xinput_user_index")
+                            .selected_text(self.settings.xinput_user_index.map_or("All".to_string(), |i| i.to_string()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.settings.xinput_user_index, None, "All");
+                                for i in 0..backend::XINPUT_USERS {
+                                    ui.selectable_value(&mut self.settings.xinput_user_index, Some(i), i.to_string());
+                                }
+                            });
+                    });
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.label(if two_devices { "Stick 1 Device:" } else { "Device:" });
+                        device_picker(ui, "stick1_device", &mut self.settings.stick1_device, &self.devices);
+                        if ui.button("Refresh").clicked() {
+                            self.devices_for = None;
+                        }
+                    });
+                }
+                if two_devices {
+                    ui.horizontal(|ui| {
+                        ui.label("Stick 2 Device:");
+                        device_picker(ui, "stick2_device", &mut self.settings.stick2_device, &self.devices);
+                    });
+                }
+
+                let all_xinput = self.settings.input_type == InputType::XInput && self.settings.xinput_user_index.is_none();
+                if dual || all_xinput {
                     ui.horizontal(|ui| {
                         ui.label("Dual Stick Priority:");
                         egui::ComboBox::from_id_source("dual_priority")
@@ -185,7 +1296,7 @@ pub fn run_settings_window() -> Result<()> {
                     ui.spacing_mut().slider_width = w;
                     
                     ui.add(
-                        egui::Slider::new(&mut self.settings.sensitivity, 0.01..=2.0).show_value(false),
+                        egui::Slider::new(&mut self.settings.sensitivity, slider("sensitivity")).show_value(false),
                     );
                 
                     ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", self.settings.sensitivity)));
@@ -198,12 +1309,237 @@ pub fn run_settings_window() -> Result<()> {
                     ui.spacing_mut().slider_width = w;
                     
                     ui.add(
-                        egui::Slider::new(&mut self.settings.deadzone, 0.0..=0.5).show_value(false),
+                        egui::Slider::new(&mut self.settings.deadzone, slider("deadzone")).show_value(false),
                     );
                 
                     ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", self.settings.deadzone)));
                 });
 
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, row_h], egui::Label::new("Outer Deadzone:"));
+    
+                    let w = (ui.available_width() - value_width).max(80.0);
+                    ui.spacing_mut().slider_width = w;
+                    
+                    ui.add(
+                        egui::Slider::new(&mut self.settings.outer_deadzone, slider("outer_deadzone")).show_value(false),
+                    );
+                
+                    ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", self.settings.outer_deadzone)));
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, row_h], egui::Label::new("Deadzone Shape:"));
+                    egui::ComboBox::from_id_source("deadzone_shape")
+                        .selected_text(self.settings.deadzone_shape.label())
+                        .show_ui(ui, |ui| {
+                            for shape in DeadzoneShape::ALL {
+                                ui.selectable_value(&mut self.settings.deadzone_shape, shape, shape.label());
+                            }
+                        });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, row_h], egui::Label::new("Response Curve:"));
+                    egui::ComboBox::from_id_source("response_curve")
+                        .selected_text(self.settings.response_curve.label())
+                        .show_ui(ui, |ui| {
+                            for curve in ResponseCurve::ALL {
+                                ui.selectable_value(&mut self.settings.response_curve, curve, curve.label());
+                            }
+                        });
+                });
+
+                let strength = match self.settings.response_curve {
+                    ResponseCurve::Power => Some("Exponent:"),
+                    ResponseCurve::Exponential | ResponseCurve::SCurve => Some("Strength:"),
+                    ResponseCurve::Linear | ResponseCurve::Custom => None,
+                };
+                if let Some(label) = strength {
+                    // The curve may have changed above, after `slider` was made
+                    let range = slider_range("curve_strength", self.settings.response_curve).expect("curve strength has a range");
+                    ui.horizontal(|ui| {
+                        ui.add_sized([label_width, row_h], egui::Label::new(label));
+
+                        let w = (ui.available_width() - value_width).max(80.0);
+                        ui.spacing_mut().slider_width = w;
+
+                        ui.add(
+                            egui::Slider::new(&mut self.settings.curve_strength, range).show_value(false),
+                        );
+
+                        ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", self.settings.curve_strength)));
+                    });
+                }
+
+                curve_editor(ui, &mut self.settings);
+
+                ui.checkbox(&mut self.settings.acceleration, "Acceleration (speed up while held at full deflection)");
+                if self.settings.acceleration {
+                    let rows: [(&str, &mut f32, std::ops::RangeInclusive<f32>); 3] = [
+                        ("Threshold:", &mut self.settings.acceleration_threshold, slider("acceleration_threshold")),
+                        ("Ramp Time (s):", &mut self.settings.acceleration_ramp_time, slider("acceleration_ramp_time")),
+                        ("Max Multiplier:", &mut self.settings.acceleration_max, slider("acceleration_max")),
+                    ];
+                    for (label, value, range) in rows {
+                        ui.horizontal(|ui| {
+                            ui.add_sized([label_width, row_h], egui::Label::new(label));
+
+                            let w = (ui.available_width() - value_width).max(80.0);
+                            ui.spacing_mut().slider_width = w;
+
+                            ui.add(egui::Slider::new(value, range).show_value(false));
+
+                            ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", value)));
+                        });
+                    }
+                }
+
+
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, row_h], egui::Label::new("Precision Mode:"));
+                    egui::ComboBox::from_id_source("precision")
+                        .selected_text(self.settings.precision.label())
+                        .show_ui(ui, |ui| {
+                            for input in PrecisionInput::ALL {
+                                ui.selectable_value(&mut self.settings.precision, input, input.label());
+                            }
+                        });
+                    match self.settings.precision {
+                        PrecisionInput::Button => {
+                            egui::ComboBox::from_id_source("precision_button")
+                                .selected_text(self.settings.precision_button.label())
+                                .show_ui(ui, |ui| {
+                                    for button in Button::ALL {
+                                        ui.selectable_value(&mut self.settings.precision_button, button, button.label());
+                                    }
+                                });
+                        }
+                        PrecisionInput::LeftTrigger | PrecisionInput::RightTrigger => {
+                            ui.checkbox(&mut self.settings.precision_analog, "Analog");
+                        }
+                        PrecisionInput::Off => {}
+                    }
+                });
+
+                if self.settings.precision != PrecisionInput::Off {
+                    ui.horizontal(|ui| {
+                        ui.add_sized([label_width, row_h], egui::Label::new("Precision Speed:"));
+
+                        let w = (ui.available_width() - value_width).max(80.0);
+                        ui.spacing_mut().slider_width = w;
+
+                        ui.add(egui::Slider::new(&mut self.settings.precision_scale, slider("precision_scale")).show_value(false));
+
+                        ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", self.settings.precision_scale)));
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.settings.gyro, "Gyro (controllers with motion sensors)");
+                    if self.settings.gyro {
+                        ui.label("Ratchet:");
+                        egui::ComboBox::from_id_source("gyro_ratchet")
+                            .selected_text(self.settings.gyro_ratchet.map_or("None", |b| b.label()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.settings.gyro_ratchet, None, "None");
+                                for button in Button::ALL {
+                                    ui.selectable_value(&mut self.settings.gyro_ratchet, Some(button), button.label());
+                                }
+                            });
+                    }
+                });
+                if self.settings.gyro {
+                    let rows: [(&str, &mut f32, std::ops::RangeInclusive<f32>); 3] = [
+                        ("Gyro Speed:", &mut self.settings.gyro_sensitivity, slider("gyro_sensitivity")),
+                        ("Smoothing:", &mut self.settings.gyro_smoothing, slider("gyro_smoothing")),
+                        ("Tightening:", &mut self.settings.gyro_tightening, slider("gyro_tightening")),
+                    ];
+                    for (label, value, range) in rows {
+                        ui.horizontal(|ui| {
+                            ui.add_sized([label_width, row_h], egui::Label::new(label));
+
+                            let w = (ui.available_width() - value_width).max(80.0);
+                            ui.spacing_mut().slider_width = w;
+
+                            ui.add(egui::Slider::new(value, range).show_value(false));
+
+                            ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", value)));
+                        });
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, row_h], egui::Label::new("Scroll With:"));
+                    egui::ComboBox::from_id_source("scroll_source")
+                        .selected_text(self.settings.scroll_source.label())
+                        .show_ui(ui, |ui| {
+                            for source in ScrollSource::ALL {
+                                ui.selectable_value(&mut self.settings.scroll_source, source, source.label());
+                            }
+                        });
+                    if self.settings.scroll_source != ScrollSource::Off {
+                        ui.checkbox(&mut self.settings.scroll_smooth, "Smooth");
+                    }
+                });
+
+                if self.settings.scroll_source != ScrollSource::Off {
+                    let mut rows: Vec<(&str, &mut f32, std::ops::RangeInclusive<f32>)> = vec![
+                        ("Scroll Speed:", &mut self.settings.scroll_speed, slider("scroll_speed")),
+                    ];
+                    if matches!(self.settings.scroll_source, ScrollSource::RightStick | ScrollSource::LeftStick) {
+                        rows.push(("Scroll Deadzone:", &mut self.settings.scroll_deadzone, slider("scroll_deadzone")));
+                    }
+                    for (label, value, range) in rows {
+                        ui.horizontal(|ui| {
+                            ui.add_sized([label_width, row_h], egui::Label::new(label));
+
+                            let w = (ui.available_width() - value_width).max(80.0);
+                            ui.spacing_mut().slider_width = w;
+
+                            ui.add(egui::Slider::new(value, range).show_value(false));
+
+                            ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", value)));
+                        });
+                    }
+                }
+
+                egui::CollapsingHeader::new("Button Mapping").show(ui, |ui| {
+                    button_mapping_editor(ui, &mut self.settings);
+
+                    let rows: [(&str, &mut f32, std::ops::RangeInclusive<f32>); 2] = [
+                        ("Hold Time (s):", &mut self.settings.hold_time, slider("hold_time")),
+                        ("Double Tap (s):", &mut self.settings.double_tap_time, slider("double_tap_time")),
+                    ];
+                    for (label, value, range) in rows {
+                        ui.horizontal(|ui| {
+                            ui.add_sized([label_width, row_h], egui::Label::new(label));
+
+                            let w = (ui.available_width() - value_width).max(80.0);
+                            ui.spacing_mut().slider_width = w;
+
+                            ui.add(egui::Slider::new(value, range).show_value(false));
+
+                            ui.add_sized([value_width, row_h], egui::Label::new(format!("{:.2}", value)));
+                        });
+                    }
+
+                    if !self.settings.layers.is_empty() {
+                        let names: Vec<String> = self.settings.layers
+                            .iter()
+                            .map(|l| format!("{} ({})", l.name, l.button.label()))
+                            .collect();
+                        ui.label(format!("Layers (edit in config.toml): {}", names.join(", ")));
+                    }
+                });
+
+                egui::CollapsingHeader::new("Input Tester").show(ui, |ui| {
+                    input_tester(ui, &self.settings, self.pad.as_ref().map(|(pad, _)| pad), &self.live);
+                });
+
+                egui::CollapsingHeader::new("Calibration").show(ui, |ui| {
+                    calibration_wizard(ui, &mut self.wizard, &self.live, &mut self.tray);
+                });
 
                 ui.separator();
 
@@ -217,8 +1553,12 @@ pub fn run_settings_window() -> Result<()> {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
                     if ui.button("Close (No Save)").clicked() {
+                        // Undo what was applied live
+                        if let Ok(saved) = load_settings() {
+                            self.push_settings(&saved);
+                        }
+                        self.closing = true;
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-
                     }
                 });
                 ui.label(format!("available_width: {:.1}", ui.available_width()));
@@ -227,6 +1567,10 @@ pub fn run_settings_window() -> Result<()> {
                     ui.label("Saved.");
                 }
             });
+
+            if !self.closing {
+                self.push_settings(&self.settings.clone());
+            }
         }
     }
 
@@ -239,12 +1583,82 @@ pub fn run_settings_window() -> Result<()> {
         ..Default::default()
     };
 
+    let tray = Client::connect().ok();
+    if tray.is_none() {
+        println!("No tray running, changes apply once saved");
+    }
+    let live_queue = match Client::connect().map_err(anyhow::Error::from).and_then(Client::subscribe) {
+        Ok(stream) => telemetry::spawn_reader(stream),
+        Err(_) => Arc::default(),
+    };
+
     eframe::run_native(
         "Settings",
         opts,
-        Box::new(|_cc| Box::new(App { settings, saved: false })),
+        Box::new(|_cc| Box::new(App {
+            settings,
+            saved: false,
+            tray,
+            pushed: String::new(),
+            devices: Vec::new(),
+            devices_for: None,
+            live_queue,
+            live: Vec::new(),
+            pad: None,
+            wizard: CalibrationWizard::default(),
+            closing: false,
+        })),
     )
     .map_err(|e| anyhow::anyhow!("eframe error: {e}"))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_directinput_configs_flip_invert_y_once() {
+        let old = "enabled = true\ninvert_y = false\nsensitivity = 1.0\ndeadzone = 0.075\ninput_type = \"DirectInputDual\"\n";
+        let mut s: Settings = toml::from_str(old).unwrap();
+        assert_eq!(s.config_version, 0);
+
+        assert!(migrate(&mut s));
+        assert!(s.invert_y);
+        assert_eq!(s.config_version, CONFIG_VERSION);
+        assert!(!migrate(&mut s));
+        assert!(s.invert_y);
+    }
+
+    #[test]
+    fn migration_leaves_other_input_types_alone() {
+        let mut s = Settings { input_type: InputType::XInput, config_version: 0, ..Settings::default() };
+        assert!(migrate(&mut s));
+        assert!(s.invert_y);
+    }
+
+    #[test]
+    fn set_field_rejects_input_types_this_system_lacks() {
+        let unavailable = if cfg!(windows) { "LegionGo" } else { "XInput" };
+        assert!(set_field(&Settings::default(), "input_type", unavailable).is_err());
+        assert_eq!(set_field(&Settings::default(), "input_type", "Dsu").unwrap().input_type, InputType::Dsu);
+    }
+
+    #[test]
+    fn set_field_holds_numbers_to_the_slider_ranges() {
+        let s = Settings::default();
+        assert_eq!(set_field(&s, "sensitivity", "0.8").unwrap().sensitivity, 0.8);
+        for (name, value) in [("sensitivity", "nan"), ("sensitivity", "-1"), ("deadzone", "1.0"), ("hold_time", "inf")] {
+            let error = set_field(&s, name, value).err().unwrap_or_else(|| panic!("{} = {} was accepted", name, value));
+            assert!(error.to_string().contains("must be between"), "{}", error);
+        }
+        // Ints are numbers too
+        assert_eq!(set_field(&s, "sensitivity", "1").unwrap().sensitivity, 1.0);
+    }
+
+    #[test]
+    fn new_settings_need_no_migration() {
+        assert!(!migrate(&mut Settings::default()));
+    }
+}
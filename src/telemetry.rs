@@ -0,0 +1,272 @@
+//! Live controller readings streamed from the tray's polling thread to
+//! settings windows subscribed over IPC. One line per message, tab
+//! separated, starting with the message type:
+//!
+//! - `raw`: device key, device name, raw left and right stick axes
+//! - `pad`: combined left and right stick axes, button bits, left and right
+//!   trigger, cursor velocity x and y, touchpad x and y (`-` for both while
+//!   it isn't touched)
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use crate::backend::Buttons;
+
+/// Raw stick readings of one controller, before calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSample {
+    /// `DeviceInfo::key`
+    pub key: String,
+    pub name: String,
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+}
+
+/// The pad as the tray's pipeline saw it on its last tick, after calibration
+/// and combining controllers, and the cursor motion it produced.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PadSample {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    /// Before shift and modifier buttons are taken out
+    pub buttons: Buttons,
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    /// Cursor speed in pixels per second, including gyro and precision mode
+    pub velocity: (f32, f32),
+    pub touchpad: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Telemetry {
+    Raw(RawSample),
+    Pad(PadSample),
+}
+
+impl Telemetry {
+    pub fn to_line(&self) -> String {
+        match self {
+            Telemetry::Raw(s) => format!(
+                "raw\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                s.key, s.name, s.left_stick.0, s.left_stick.1, s.right_stick.0, s.right_stick.1
+            ),
+            Telemetry::Pad(p) => format!(
+                "pad\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                p.left_stick.0,
+                p.left_stick.1,
+                p.right_stick.0,
+                p.right_stick.1,
+                p.buttons.bits(),
+                p.left_trigger,
+                p.right_trigger,
+                p.velocity.0,
+                p.velocity.1,
+                p.touchpad.map_or("-\t-".to_string(), |(x, y)| format!("{}\t{}", x, y))
+            ),
+        }
+    }
+
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let mut fields = line.trim_end().split('\t');
+        match fields.next()? {
+            "raw" => {
+                let key = fields.next()?.to_string();
+                let name = fields.next()?.to_string();
+                let mut axis = || fields.next()?.parse::<f32>().ok();
+                Some(Telemetry::Raw(RawSample {
+                    key,
+                    name,
+                    left_stick: (axis()?, axis()?),
+                    right_stick: (axis()?, axis()?),
+                }))
+            }
+            "pad" => {
+                let values: Vec<&str> = fields.collect();
+                let [lx, ly, rx, ry, buttons, lt, rt, vx, vy, tx, ty] = values.as_slice() else {
+                    return None;
+                };
+                let num = |s: &str| s.parse::<f32>().ok();
+                let touchpad = match (*tx, *ty) {
+                    ("-", "-") => None,
+                    (tx, ty) => Some((num(tx)?, num(ty)?)),
+                };
+                Some(Telemetry::Pad(PadSample {
+                    left_stick: (num(lx)?, num(ly)?),
+                    right_stick: (num(rx)?, num(ry)?),
+                    buttons: Buttons::from_bits(buttons.parse().ok()?),
+                    left_trigger: num(lt)?,
+                    right_trigger: num(rt)?,
+                    velocity: (num(vx)?, num(vy)?),
+                    touchpad,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Batches waiting for a subscriber's writer thread; newer ones are dropped
+/// while it's this far behind.
+const BACKLOG: usize = 8;
+
+/// Tray side: every connection that asked for telemetry. Each one is written
+/// on a thread of its own, so a window that stops reading can't hold up the
+/// polling thread.
+#[derive(Clone, Default)]
+pub struct TelemetryFeed {
+    subscribers: Arc<Mutex<Vec<SyncSender<Arc<str>>>>>,
+}
+
+impl TelemetryFeed {
+    pub fn subscribe(&self, mut stream: Box<dyn Write + Send>) {
+        let (sender, batches) = mpsc::sync_channel::<Arc<str>>(BACKLOG);
+        std::thread::spawn(move || {
+            for text in batches {
+                if stream.write_all(text.as_bytes()).and_then(|_| stream.flush()).is_err() {
+                    // Hung up; `send` notices the closed channel next time
+                    break;
+                }
+            }
+        });
+        self.subscribers.lock().unwrap().push(sender);
+    }
+
+    pub fn is_attached(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Queues `messages` for every subscriber without waiting on any of them,
+    /// dropping the ones that hung up.
+    pub fn send(&self, messages: &[Telemetry]) {
+        let text: Arc<str> = messages.iter().map(Telemetry::to_line).collect::<String>().into();
+        self.subscribers.lock().unwrap().retain(|sender| match sender.try_send(text.clone()) {
+            // A subscriber that's behind just misses this batch
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+/// Messages kept for the settings window between frames; older ones are
+/// dropped if it doesn't get around to reading them.
+const QUEUE_LEN: usize = 600;
+
+/// Settings side: reads messages from a subscribed connection on a
+/// background thread, until the tray goes away.
+pub fn spawn_reader(stream: impl Read + Send + 'static) -> Arc<Mutex<VecDeque<Telemetry>>> {
+    let queue = Arc::new(Mutex::new(VecDeque::new()));
+    let writer = queue.clone();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if let Some(message) = Telemetry::parse_line(&line) {
+                let mut queue = writer.lock().unwrap();
+                if queue.len() >= QUEUE_LEN {
+                    queue.pop_front();
+                }
+                queue.push_back(message);
+            }
+        }
+    });
+    queue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    fn pad() -> Telemetry {
+        Telemetry::Pad(PadSample { left_stick: (0.5, -1.0), velocity: (120.0, 0.0), ..Default::default() })
+    }
+
+    /// Stands in for a window that never reads: every write blocks until the test ends.
+    struct Stuck(Mutex<mpsc::Receiver<()>>);
+
+    impl Write for Stuck {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            let _ = self.0.lock().unwrap().recv();
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Records what it's sent, or fails every write once `broken`.
+    #[derive(Clone, Default)]
+    struct Shared {
+        written: Arc<Mutex<Vec<u8>>>,
+        broken: bool,
+    }
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.broken {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn wait_for(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn lines_parse_back_to_the_same_message() {
+        let raw = Telemetry::Raw(RawSample {
+            key: "dsu:0".to_string(),
+            name: "DSU slot 0".to_string(),
+            left_stick: (1.0, 2.0),
+            right_stick: (-3.0, 4.5),
+        });
+        let touched = Telemetry::Pad(PadSample { touchpad: Some((0.25, 1.0)), ..Default::default() });
+        for message in [raw, pad(), touched] {
+            assert_eq!(Telemetry::parse_line(&message.to_line()), Some(message));
+        }
+        assert_eq!(Telemetry::parse_line("pad\t1\t2\n"), None);
+    }
+
+    #[test]
+    fn a_subscriber_that_stops_reading_does_not_block_send() {
+        let feed = TelemetryFeed::default();
+        let (_release, stuck) = mpsc::channel();
+        feed.subscribe(Box::new(Stuck(Mutex::new(stuck))));
+        let reader = Shared::default();
+        feed.subscribe(Box::new(reader.clone()));
+
+        let start = Instant::now();
+        for _ in 0..BACKLOG * 4 {
+            feed.send(&[pad()]);
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(feed.is_attached());
+        wait_for(|| !reader.written.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn subscribers_that_hung_up_are_dropped() {
+        let feed = TelemetryFeed::default();
+        feed.subscribe(Box::new(Shared { broken: true, ..Default::default() }));
+        wait_for(|| {
+            feed.send(&[pad()]);
+            !feed.is_attached()
+        });
+    }
+}
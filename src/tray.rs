@@ -4,15 +4,39 @@ use tray_icon::{
     Icon, TrayIconBuilder,
 };
 use tao::event_loop::{ControlFlow, EventLoopBuilder};
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex};
 use tao::event::Event;
 
-use crate::inputs;
+use crate::calibration;
+use crate::inputs::{self, DeviceEvent};
+use crate::ipc::{self, TrayState};
 use crate::settings;
+use crate::telemetry::TelemetryFeed;
 
 #[derive(Debug, Clone)]
 pub enum TrayEvent {
-    ReloadSettings,
+    /// The settings window exited; `saved` when config.toml changed meanwhile
+    SettingsClosed { saved: bool },
+    /// Sent by the polling thread, `None` when back on the base mappings
+    LayerChanged(Option<String>),
+    /// Sent by the polling thread when a controller is connected or lost
+    Device(DeviceEvent),
+}
+
+/// Tray tooltip listing the active layer and the connected controllers.
+fn tooltip(layer: Option<&str>, devices: &[String]) -> String {
+    let mut text = match layer {
+        Some(name) => format!("Controller Tray - {}", name),
+        None => "Controller Tray".to_string(),
+    };
+    if devices.is_empty() {
+        text.push_str("\nNo controller connected");
+    }
+    for device in devices {
+        text.push('\n');
+        text.push_str(device);
+    }
+    text
 }
 
 pub fn load_icon_from_png(path: &str) -> Result<Icon> {
@@ -25,7 +49,26 @@ pub fn load_icon_from_png(path: &str) -> Result<Icon> {
 pub fn run_tray() -> Result<()>{
     let initial =  Arc::new(Mutex::new(settings::load_settings()?));
 
-    inputs::spawn_polling_thread(initial.clone());
+    // let event_loop = EventLoopBuilder::new().build();
+    let event_loop = EventLoopBuilder::<TrayEvent>::with_user_event().build();
+    let proxy = event_loop.create_proxy();
+
+    let calibrations = Arc::new(Mutex::new(calibration::load_calibrations_or_default()));
+
+    let telemetry = TelemetryFeed::default();
+    let (device_queries, device_query_rx) = mpsc::channel();
+
+    inputs::spawn_polling_thread(initial.clone(), calibrations.clone(), telemetry.clone(), device_query_rx, proxy.clone());
+
+    let ipc_state = TrayState {
+        settings: initial.clone(),
+        calibrations: calibrations.clone(),
+        telemetry,
+        device_queries,
+    };
+    if let Err(e) = ipc::spawn_server(ipc_state) {
+        println!("Failed to start IPC, settings will apply when their window closes: {}", e);
+    }
  
     let menu = Menu::new();
     let exit_item = MenuItem::new("Exit", true, None);
@@ -37,26 +80,48 @@ pub fn run_tray() -> Result<()>{
     // menu.append(&reload_item)?;
     
     let icon = load_icon_from_png("assets/game-controller.png")?;
-    let _tray = TrayIconBuilder::new()
+    let tray = TrayIconBuilder::new()
         .with_menu(Box::new(menu))
         .with_tooltip("Controller Tray")
         .with_icon(icon)
         .build();
 
-    // let event_loop = EventLoopBuilder::new().build();
-    let event_loop = EventLoopBuilder::<TrayEvent>::with_user_event().build();
-    let proxy = event_loop.create_proxy();
-    
     let menu_channel = MenuEvent::receiver();
+    let mut layer: Option<String> = None;
+    let mut devices: Vec<String> = Vec::new();
 
     event_loop.run(move |event,_target, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         match event {
-            Event::UserEvent(TrayEvent::ReloadSettings) => {
-                if let Ok(new_s) = settings::load_settings() {
-                    *initial.lock().unwrap() = new_s;
-                    // optionally update UI text here (toggle labels, etc.)
+            Event::UserEvent(TrayEvent::SettingsClosed { saved }) => {
+                // Unless the window saved, the tray already has its settings
+                // through IPC, and reloading would undo changes made by ctl
+                if saved {
+                    if let Ok(new_s) = settings::load_settings() {
+                        *initial.lock().unwrap() = new_s;
+                        // optionally update UI text here (toggle labels, etc.)
+                    }
+                }
+                *calibrations.lock().unwrap() = calibration::load_calibrations_or_default();
+            }
+            Event::UserEvent(TrayEvent::LayerChanged(name)) => {
+                layer = name;
+                if let Ok(tray) = &tray {
+                    let _ = tray.set_tooltip(Some(tooltip(layer.as_deref(), &devices)));
+                }
+            }
+            Event::UserEvent(TrayEvent::Device(event)) => {
+                match event {
+                    DeviceEvent::Connected(name) => devices.push(name),
+                    DeviceEvent::Disconnected(name) => {
+                        if let Some(i) = devices.iter().position(|d| *d == name) {
+                            devices.remove(i);
+                        }
+                    }
+                }
+                if let Ok(tray) = &tray {
+                    let _ = tray.set_tooltip(Some(tooltip(layer.as_deref(), &devices)));
                 }
             }
             _ => {}